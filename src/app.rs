@@ -1,14 +1,92 @@
 use arboard::Clipboard;
-use ratatui::widgets::ListState;
+use ratatui::{layout::Rect, text::Line, widgets::ListState};
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
+use crate::cli::Args;
+use crate::command::{self, CommandPrompt, ExCommand, SetOption};
+use crate::config::Config;
+use crate::finder::Finder;
+use crate::grep::ProjectSearch;
+use crate::keymap::{Action, Context, KeyPress, Lookup};
+use crate::renderer::{
+    HeadingEntry, LinkEntry, Rendered, SourceMap, highlight_source, line_index, markdown_to_html,
+    markdown_to_ratatui, selected_source, selected_text,
+};
+use crate::search::{Search, SearchMatch};
+use crate::tree::{FileTree, normalize_path};
+use crate::watcher::FileWatcher;
+
+/// Mouse selection in the preview, in rendered-document coordinates
+/// (rows include the scroll offset at the time of selecting).
 #[derive(Clone, Copy, Debug)]
 pub struct SelectionRange {
     pub start: (u16, u16), // (col, row)
     pub end: (u16, u16),   // (col, row)
 }
 
+/// The `/` or `?` prompt while a search pattern is being typed.
+pub struct SearchPrompt {
+    pub input: String,
+    pub forward: bool,
+    /// Scroll offset when the prompt was opened, restored on cancel.
+    pub origin: u16,
+}
+
+/// A message in the status line, cleared by the next key press.
+pub struct StatusMessage {
+    pub text: String,
+    pub is_error: bool,
+}
+
+/// A position in the link navigation history.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub file: Option<PathBuf>,
+    pub scroll_offset: u16,
+}
+
+/// An open document. The one shown lives in `App`'s own fields; its entry
+/// here is brought up to date when another document is shown.
+pub struct Buffer {
+    pub path: PathBuf,
+    markdown: String,
+    scroll_offset: u16,
+    cursor_line: usize,
+    selection: Option<SelectionRange>,
+}
+
+/// The rendered document and what it was rendered for.
+pub struct RenderCache {
+    /// (document revision, theme, preview width, collapsed callouts unfolded)
+    key: (u64, String, u16, bool),
+    pub rendered: Rendered,
+}
+
+/// Where `zt` / `zz` / `zb` put the cursor line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreenPosition {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// Whether the raw markdown is shown, and where.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SourceView {
+    #[default]
+    Hidden,
+    /// Next to the preview.
+    Split,
+    /// In place of the preview.
+    Only,
+}
+
 /// Which pane currently has focus for navigation/scrolling.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FocusedPane {
@@ -17,13 +95,44 @@ pub enum FocusedPane {
 }
 
 pub struct App {
-    pub files: Vec<PathBuf>,
+    pub tree: FileTree,
+    /// Index of the selected row among the visible tree nodes.
     pub selected: usize,
+    /// Scroll state of the tree list, kept across frames so clicks map onto rows.
+    pub tree_state: ListState,
     pub markdown: String,
+    /// Bumped whenever `markdown` changes, to invalidate the render cache.
+    pub revision: u64,
+    pub render_cache: Option<RenderCache>,
+    /// Show the content of collapsed callouts (`zi`).
+    pub unfold: bool,
+    pub source_view: SourceView,
+    /// Highlighted source lines, and the revision and theme they are for.
+    source_cache: Option<(u64, String, Vec<Line<'static>>)>,
+    /// Path of the document shown in the preview, if any.
+    pub current_file: Option<PathBuf>,
+    /// Git branch of the open file (or the tree root), for the status line.
+    pub git_branch: Option<String>,
+    /// Open documents, in the order they were opened.
+    pub buffers: Vec<Buffer>,
+    /// Index of the shown document in `buffers`.
+    pub current_buffer: usize,
+    /// Whether to show the buffer picker popup.
+    pub show_buffer_list: bool,
+    /// State for the buffer list.
+    pub buffer_list_state: ListState,
     pub show_tree: bool,
     pub scroll_offset: u16,
     pub max_scroll: u16,
-    pub last_key: Option<char>,
+    /// Rendered line the preview cursor is on; always kept on screen.
+    pub cursor_line: usize,
+    /// Number of rendered lines in the document, as of the last draw.
+    line_count: usize,
+    /// Count typed before a motion, as in `5j`.
+    pub count: Option<usize>,
+    /// Keys typed so far of a multi-key binding such as `gg`.
+    pub pending_keys: Vec<KeyPress>,
+    pending_since: Option<Instant>,
     /// Which pane is currently focused when the tree is visible.
     pub focused_pane: FocusedPane,
     /// Percentage of the screen width used by the file tree (10–80).
@@ -32,6 +141,10 @@ pub struct App {
     pub last_area_width: u16,
     /// Last rendered tree width in columns.
     pub last_tree_width_px: u16,
+    /// Last rendered preview area (including borders), used to re-render for copying.
+    pub last_preview_area: Rect,
+    /// Number of text rows visible in the preview.
+    pub viewport_height: u16,
     /// Whether the user is currently dragging the tree/preview divider.
     pub dragging_divider: bool,
     /// Last mouse click time for double-click detection (milliseconds since epoch).
@@ -46,66 +159,262 @@ pub struct App {
     pub show_theme_list: bool,
     /// State for the theme selection list.
     pub theme_list_state: ListState,
+    /// Headings of the rendered document, updated whenever it is re-rendered.
+    pub headings: Vec<HeadingEntry>,
+    /// The Ctrl+p fuzzy finder, while open.
+    pub finder: Option<Finder>,
+    /// The project-wide search popup, while open.
+    pub project_search: Option<ProjectSearch>,
+    /// Whether to show the outline (table of contents) popup.
+    pub show_outline: bool,
+    /// State for the outline list.
+    pub outline_state: ListState,
 
     // Text Selection
     pub selection: Option<SelectionRange>,
     // Clipboard
-    #[allow(dead_code)] // Keep it for now even if only used in methods
     pub clipboard: Option<Clipboard>,
+
+    // In-document search
+    pub search: Option<Search>,
+    pub search_prompt: Option<SearchPrompt>,
+    /// Message shown in the status line (errors and command results).
+    pub status_message: Option<StatusMessage>,
+
+    /// The `:` prompt while a command is being typed.
+    pub command_prompt: Option<CommandPrompt>,
+    /// Commands entered at the `:` prompt, oldest first.
+    pub command_history: Vec<String>,
+
+    /// Links of the rendered document, updated whenever it is re-rendered.
+    pub links: Vec<LinkEntry>,
+    /// Link selected with Tab, followed with Enter.
+    pub focused_link: Option<usize>,
+    /// Footnote reference link (URL and line) last jumped from with `gd`.
+    footnote_origin: Option<(String, usize)>,
+    /// Positions to return to with Ctrl+o.
+    pub back_history: Vec<HistoryEntry>,
+    /// Positions to go forward to again with Ctrl+i.
+    pub forward_history: Vec<HistoryEntry>,
+    /// Command used to open external links; platform default if `None`.
+    pub opener: Option<String>,
+    /// Set by `e`; the main loop hands the terminal to `$EDITOR`.
+    pub edit_requested: bool,
+
+    /// Filesystem watcher for live reload; `None` if unavailable.
+    pub watcher: Option<FileWatcher>,
+
+    /// Settings from `config.toml`; command-line options take precedence.
+    pub config: Config,
 }
 
 impl App {
-    pub fn new() -> Self {
-        let mut files: Vec<PathBuf> = fs::read_dir(".")
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .collect();
-
-        // Sort files by name (case-insensitive)
-        files.sort_by(|a, b| {
-            let a_name = a
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_lowercase();
-            let b_name = b
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_lowercase();
-            a_name.cmp(&b_name)
-        });
+    pub fn new(args: &Args, config: Config) -> io::Result<Self> {
+        // A file argument opens that file with its directory as the tree root;
+        // a directory argument (or none) shows its README.md if present.
+        let (root, file) = match &args.path {
+            Some(path) if path.is_file() => {
+                let root = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                    _ => PathBuf::from("."),
+                };
+                // Spelled like the tree's nodes (`./README.md`) so it gets selected.
+                let file = match path.file_name() {
+                    Some(name) => root.join(name),
+                    None => path.clone(),
+                };
+                (root, Some(file))
+            }
+            Some(dir) => (dir.clone(), None),
+            None => (PathBuf::from("."), None),
+        };
 
-        let markdown =
-            fs::read_to_string("README.md").unwrap_or_else(|_| "No README.md found".to_string());
+        let tree = FileTree::new(&root).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("cannot read directory '{}': {}", root.display(), e),
+            )
+        })?;
 
-        Self {
-            files,
+        let show_tree = config.tree.visible && !args.no_tree;
+        let mut app = Self {
+            tree,
             selected: 0,
-            markdown,
-            show_tree: true,
+            tree_state: ListState::default(),
+            markdown: "No README.md found".to_string(),
+            revision: 0,
+            render_cache: None,
+            unfold: false,
+            source_view: SourceView::default(),
+            source_cache: None,
+            current_file: None,
+            git_branch: None,
+            buffers: Vec::new(),
+            current_buffer: 0,
+            show_buffer_list: false,
+            buffer_list_state: ListState::default(),
+            show_tree,
             scroll_offset: 0,
             max_scroll: 0,
-            last_key: None,
-            focused_pane: FocusedPane::FileTree,
-            tree_width_percentage: 20,
+            cursor_line: 0,
+            line_count: 0,
+            count: None,
+            pending_keys: Vec::new(),
+            pending_since: None,
+            focused_pane: if show_tree {
+                FocusedPane::FileTree
+            } else {
+                FocusedPane::Preview
+            },
+            tree_width_percentage: args.tree_width.unwrap_or(config.tree.width),
             last_area_width: 0,
             last_tree_width_px: 0,
+            last_preview_area: Rect::default(),
+            viewport_height: 0,
             dragging_divider: false,
             last_click_time: None,
             last_clicked_file: None,
-            current_theme: "TokyoNight".to_string(),
+            current_theme: args.theme.clone().unwrap_or_else(|| config.theme.clone()),
             available_themes: crate::syntax::get_available_themes(),
             show_theme_list: false,
             theme_list_state: ListState::default(),
+            headings: Vec::new(),
+            finder: None,
+            project_search: None,
+            show_outline: false,
+            outline_state: ListState::default(),
             selection: None,
             clipboard: Clipboard::new().ok(),
+            search: None,
+            search_prompt: None,
+            status_message: None,
+            command_prompt: None,
+            command_history: Vec::new(),
+            links: Vec::new(),
+            focused_link: None,
+            footnote_origin: None,
+            back_history: Vec::new(),
+            forward_history: Vec::new(),
+            opener: args.opener.clone().or_else(|| config.opener.clone()),
+            edit_requested: false,
+            watcher: FileWatcher::new(),
+            config,
+        };
+
+        if let Some(file) = file {
+            app.open_file(&file);
+            if let Some(row) = app.tree.row_of(&file) {
+                app.selected = row;
+            }
+        } else {
+            let readme = root.join("README.md");
+            if readme.is_file() {
+                app.open_file(&readme);
+                // Keep the tree focused when starting on a directory.
+                app.focused_pane = if show_tree {
+                    FocusedPane::FileTree
+                } else {
+                    FocusedPane::Preview
+                };
+            }
         }
+
+        app.update_git_branch();
+        app.sync_watches();
+        Ok(app)
     }
 
-    pub fn selected_file(&self) -> Option<&PathBuf> {
-        self.files.get(self.selected)
+    /// Binding contexts for the current state, most specific first.
+    fn key_contexts(&self) -> &'static [Context] {
+        if self.show_theme_list || self.show_outline || self.show_buffer_list {
+            &[Context::Popup]
+        } else if self.show_tree && self.focused_pane == FocusedPane::FileTree {
+            &[Context::Tree, Context::Global]
+        } else {
+            &[Context::Preview, Context::Global]
+        }
+    }
+
+    /// Feed a key press through the keymap and return the actions to run.
+    ///
+    /// A key that doesn't continue the pending sequence ends it: the keys
+    /// typed so far run their own binding (if any) and the key is looked
+    /// up again on its own.
+    pub fn press_key(&mut self, key: KeyPress) -> Vec<Action> {
+        // Digits before a binding are a count; a leading 0 is not.
+        if self.pending_keys.is_empty()
+            && !self.show_theme_list
+            && !self.show_outline
+            && !self.show_buffer_list
+            && let Some(digit) = key.digit()
+            && (digit > 0 || self.count.is_some())
+        {
+            let count = self.count.unwrap_or(0).saturating_mul(10);
+            self.count = Some(count.saturating_add(digit).min(999_999));
+            return Vec::new();
+        }
+
+        self.pending_keys.push(key);
+        match self
+            .config
+            .keymap
+            .lookup(self.key_contexts(), &self.pending_keys)
+        {
+            Lookup::Action(action) => {
+                self.clear_pending_keys();
+                vec![action]
+            }
+            Lookup::Pending(_) => {
+                self.pending_since = Some(Instant::now());
+                Vec::new()
+            }
+            Lookup::NotFound => {
+                self.pending_keys.pop();
+                if self.pending_keys.is_empty() {
+                    self.count = None;
+                    return Vec::new();
+                }
+                let mut actions = Vec::new();
+                if let Lookup::Pending(Some(action)) = self
+                    .config
+                    .keymap
+                    .lookup(self.key_contexts(), &self.pending_keys)
+                {
+                    actions.push(action);
+                }
+                self.clear_pending_keys();
+                actions.extend(self.press_key(key));
+                actions
+            }
+        }
+    }
+
+    /// Time left before the pending key sequence times out.
+    pub fn pending_key_timeout(&self) -> Option<Duration> {
+        let since = self.pending_since?;
+        Some(Duration::from_millis(self.config.keymap.timeout).saturating_sub(since.elapsed()))
+    }
+
+    /// End a pending sequence that has timed out, returning the action bound
+    /// to the keys typed so far, if any.
+    pub fn expire_pending_keys(&mut self) -> Option<Action> {
+        if !self.pending_key_timeout()?.is_zero() {
+            return None;
+        }
+        let lookup = self
+            .config
+            .keymap
+            .lookup(self.key_contexts(), &self.pending_keys);
+        self.clear_pending_keys();
+        match lookup {
+            Lookup::Pending(action) => action,
+            _ => None,
+        }
+    }
+
+    pub fn clear_pending_keys(&mut self) {
+        self.pending_keys.clear();
+        self.pending_since = None;
     }
 
     pub fn toggle_tree(&mut self) {
@@ -175,74 +484,447 @@ impl App {
     }
 
     pub fn next_file(&mut self) {
-        if self.selected + 1 < self.files.len() {
+        if self.selected + 1 < self.tree.len() {
             self.selected += 1;
         }
-        self.last_key = None;
     }
 
     pub fn prev_file(&mut self) {
         if self.selected > 0 {
             self.selected -= 1;
         }
-        self.last_key = None;
     }
 
+    /// Scroll the view only (mouse wheel); the cursor stays on screen.
     pub fn scroll_down(&mut self, amount: u16) {
         self.scroll_offset = self
             .scroll_offset
             .saturating_add(amount)
             .min(self.max_scroll);
-        self.last_key = None;
+        self.clamp_cursor_to_view();
     }
 
     pub fn scroll_up(&mut self, amount: u16) {
         self.scroll_offset = self.scroll_offset.saturating_sub(amount);
-        self.last_key = None;
+        self.clamp_cursor_to_view();
+    }
+
+    /// Move the cursor by `delta` lines and scroll the view with it (`j`,
+    /// `k`, `Ctrl+d`, ...). The cursor keeps its place on screen until the
+    /// view reaches either end of the document.
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.line_count.saturating_sub(1);
+        self.cursor_line = self.cursor_line.saturating_add_signed(delta).min(last);
+        let scroll = (self.scroll_offset as isize).saturating_add(delta);
+        self.scroll_offset = scroll.clamp(0, self.max_scroll as isize) as u16;
+        self.scroll_to_cursor();
+    }
+
+    /// Lines moved by `Ctrl+d` / `Ctrl+u`: the count, the configured step,
+    /// or half the preview.
+    pub fn half_page(&self, count: Option<usize>) -> usize {
+        match (count, self.config.scroll.half_page) {
+            (Some(count), _) => count,
+            (None, 0) => (self.viewport_height as usize / 2).max(1),
+            (None, step) => step as usize,
+        }
+    }
+
+    /// Lines moved by `PageDown` / `PageUp`: the configured step, or the
+    /// preview height less two lines of context.
+    pub fn page(&self) -> usize {
+        match self.config.scroll.page {
+            0 => (self.viewport_height as usize).saturating_sub(2).max(1),
+            step => step as usize,
+        }
     }
 
     pub fn scroll_to_top(&mut self) {
         self.scroll_offset = 0;
-        self.last_key = None;
+        self.cursor_line = 0;
     }
 
     pub fn scroll_to_bottom(&mut self) {
         self.scroll_offset = self.max_scroll;
-        self.last_key = None;
+        self.cursor_line = self.line_count.saturating_sub(1);
+    }
+
+    /// Put the cursor on rendered line `line` (1-based, as in `10G`).
+    pub fn goto_line(&mut self, line: usize) {
+        self.cursor_line = line
+            .saturating_sub(1)
+            .min(self.line_count.saturating_sub(1));
+        self.reveal_line(self.cursor_line);
+    }
+
+    /// Go to `percent`% of the document (`50%`).
+    pub fn goto_percent(&mut self, percent: usize) {
+        let percent = percent.min(100);
+        self.goto_line((percent * self.line_count).div_ceil(100));
+    }
+
+    /// Move to the start of the `count`th next block (`}`).
+    pub fn next_block(&mut self, count: usize) {
+        let Some(rendered) = self.rendered() else {
+            return;
+        };
+        let cursor = self.cursor_line;
+        let target = rendered
+            .blocks
+            .iter()
+            .filter(|&&b| b > cursor)
+            .nth(count - 1)
+            .copied()
+            .unwrap_or(self.line_count.saturating_sub(1));
+        self.cursor_line = target;
+        self.scroll_to_cursor();
+    }
+
+    /// Move to the start of the `count`th previous block (`{`).
+    pub fn prev_block(&mut self, count: usize) {
+        let Some(rendered) = self.rendered() else {
+            return;
+        };
+        let cursor = self.cursor_line;
+        let target = rendered
+            .blocks
+            .iter()
+            .rev()
+            .filter(|&&b| b < cursor)
+            .nth(count - 1)
+            .copied()
+            .unwrap_or(0);
+        self.cursor_line = target;
+        self.scroll_to_cursor();
+    }
+
+    /// Last document line currently on screen.
+    fn bottom_line(&self) -> usize {
+        (self.scroll_offset as usize + self.viewport_height.max(1) as usize - 1)
+            .min(self.line_count.saturating_sub(1))
+    }
+
+    /// Cursor to the `count`th line from the top of the screen (`H`).
+    pub fn cursor_to_screen_top(&mut self, count: usize) {
+        let line = self.scroll_offset as usize + count - 1;
+        self.cursor_line = line.min(self.bottom_line());
+    }
+
+    /// Cursor to the middle of the screen (`M`).
+    pub fn cursor_to_screen_middle(&mut self) {
+        let top = self.scroll_offset as usize;
+        self.cursor_line = top + (self.bottom_line() - top) / 2;
+    }
+
+    /// Cursor to the `count`th line from the bottom of the screen (`L`).
+    pub fn cursor_to_screen_bottom(&mut self, count: usize) {
+        let line = self.bottom_line().saturating_sub(count - 1);
+        self.cursor_line = line.max(self.scroll_offset as usize);
+    }
+
+    /// Scroll so the cursor line is at the top (`zt`), center (`zz`) or
+    /// bottom (`zb`) of the screen.
+    pub fn scroll_cursor_to(&mut self, position: ScreenPosition) {
+        let height = self.viewport_height.max(1) as usize;
+        let above = match position {
+            ScreenPosition::Top => 0,
+            ScreenPosition::Center => height / 2,
+            ScreenPosition::Bottom => height - 1,
+        };
+        let top = self.cursor_line.saturating_sub(above);
+        self.scroll_offset = top.min(self.max_scroll as usize) as u16;
     }
 
+    /// Scroll the least amount that brings the cursor on screen.
+    fn scroll_to_cursor(&mut self) {
+        let height = self.viewport_height.max(1) as usize;
+        let top = self.scroll_offset as usize;
+        if self.cursor_line < top {
+            self.scroll_offset = self.cursor_line as u16;
+        } else if self.cursor_line >= top + height {
+            let top = self.cursor_line + 1 - height;
+            self.scroll_offset = top.min(self.max_scroll as usize) as u16;
+        }
+    }
+
+    /// Pull the cursor back on screen after the view moved without it.
+    fn clamp_cursor_to_view(&mut self) {
+        self.cursor_line = self.cursor_line.clamp(
+            self.scroll_offset as usize,
+            self.bottom_line().max(self.scroll_offset as usize),
+        );
+    }
+
+    /// Open the selected file, or expand/collapse the selected directory.
     pub fn open_selected_file(&mut self) {
-        if let Some(file) = self.selected_file() {
-            if file.is_file() {
-                self.markdown =
-                    fs::read_to_string(file).unwrap_or_else(|_| "Unable to read file".to_string());
+        let Some(node) = self.tree.node_at(self.selected) else {
+            return;
+        };
+
+        if node.is_dir {
+            self.tree.toggle(self.selected);
+            self.sync_watches();
+        } else if node.path.is_file() {
+            let path = node.path.clone();
+            self.open_file(&path);
+        }
+    }
+
+    /// Open `path` in the preview, recording the current position in the history.
+    pub fn open_file(&mut self, path: &Path) {
+        if self.current_file.is_some() {
+            self.push_history();
+        }
+        self.load_file(path);
+    }
+
+    /// Load `path` into the preview and focus it. A document that is already
+    /// open comes back where it was left.
+    fn load_file(&mut self, path: &Path) {
+        self.save_buffer();
+        let markdown = fs::read_to_string(path);
+        // The same file may be reached as `README.md` and `./README.md`.
+        let normalized = normalize_path(path);
+        match (self.buffers.iter()).position(|b| normalize_path(&b.path) == normalized) {
+            Some(i) => {
+                let buffer = &self.buffers[i];
+                self.current_buffer = i;
+                self.scroll_offset = buffer.scroll_offset;
+                self.cursor_line = buffer.cursor_line;
+                self.selection = buffer.selection;
+                // Pick up changes made while it was hidden.
+                let markdown = markdown.unwrap_or_else(|_| buffer.markdown.clone());
+                self.set_markdown(markdown);
+            }
+            None => {
+                self.buffers.push(Buffer {
+                    path: path.to_path_buf(),
+                    markdown: String::new(),
+                    scroll_offset: 0,
+                    cursor_line: 0,
+                    selection: None,
+                });
+                self.current_buffer = self.buffers.len() - 1;
                 self.scroll_offset = 0;
-                // After opening a file, shift focus to the preview.
-                self.focus_preview();
+                self.cursor_line = 0;
+                self.selection = None;
+                let markdown = markdown.unwrap_or_else(|e| {
+                    self.show_error(format!("Can't read {}: {}", path.display(), e));
+                    "Unable to read file".to_string()
+                });
+                self.set_markdown(markdown);
+            }
+        }
+        self.current_file = Some(self.buffers[self.current_buffer].path.clone());
+        self.focused_link = None;
+        self.update_git_branch();
+        // After opening a file, shift focus to the preview.
+        self.focus_preview();
+        self.sync_watches();
+    }
+
+    fn update_git_branch(&mut self) {
+        let path = self.current_file.as_deref().unwrap_or(&self.tree.root);
+        self.git_branch = crate::git::branch(path);
+    }
+
+    /// Store the shown document's content and position in its buffer.
+    fn save_buffer(&mut self) {
+        if let Some(buffer) = self.buffers.get_mut(self.current_buffer)
+            && self.current_file.as_ref() == Some(&buffer.path)
+        {
+            buffer.markdown = self.markdown.clone();
+            buffer.scroll_offset = self.scroll_offset;
+            buffer.cursor_line = self.cursor_line;
+            buffer.selection = self.selection;
+        }
+    }
+
+    /// Show the buffer at `index` (counted from 0), selecting it in the tree
+    /// if it is listed there.
+    pub fn switch_buffer(&mut self, index: usize) {
+        if let Some(buffer) = self.buffers.get(index) {
+            let path = buffer.path.clone();
+            self.load_file(&path);
+            if let Some(row) = self.tree.row_of(&path) {
+                self.selected = row;
+            }
+        }
+    }
+
+    /// Show the `count`th next buffer, wrapping around (`gt`, `:bnext`).
+    pub fn next_buffer(&mut self, count: usize) {
+        if !self.buffers.is_empty() {
+            self.switch_buffer((self.current_buffer + count) % self.buffers.len());
+        }
+    }
+
+    /// Show the `count`th previous buffer, wrapping around (`gT`, `:bprevious`).
+    pub fn prev_buffer(&mut self, count: usize) {
+        let len = self.buffers.len();
+        if len > 0 {
+            self.switch_buffer((self.current_buffer + len - count % len) % len);
+        }
+    }
+
+    /// Close the shown document and show the next one (`:bdelete`).
+    pub fn close_buffer(&mut self) {
+        if self.current_file.is_none() || self.current_buffer >= self.buffers.len() {
+            self.show_error("No file name".to_string());
+            return;
+        }
+        self.buffers.remove(self.current_buffer);
+        if self.buffers.is_empty() {
+            self.current_file = None;
+            self.current_buffer = 0;
+            self.scroll_offset = 0;
+            self.cursor_line = 0;
+            self.selection = None;
+            self.focused_link = None;
+            self.set_markdown("No file open".to_string());
+            self.update_git_branch();
+            self.sync_watches();
+        } else {
+            // Nothing to save: the closed document's fields are replaced.
+            self.current_file = None;
+            self.switch_buffer(self.current_buffer.min(self.buffers.len() - 1));
+        }
+    }
+
+    pub fn toggle_buffer_list(&mut self) {
+        self.show_buffer_list = !self.show_buffer_list;
+        if self.show_buffer_list {
+            self.buffer_list_state.select(Some(self.current_buffer));
+        }
+    }
+
+    pub fn next_buffer_selection(&mut self) {
+        if self.buffers.is_empty() {
+            return;
+        }
+        let i = match self.buffer_list_state.selected() {
+            Some(i) if i + 1 < self.buffers.len() => i + 1,
+            _ => 0,
+        };
+        self.buffer_list_state.select(Some(i));
+    }
+
+    pub fn prev_buffer_selection(&mut self) {
+        if self.buffers.is_empty() {
+            return;
+        }
+        let i = match self.buffer_list_state.selected() {
+            Some(0) | None => self.buffers.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.buffer_list_state.select(Some(i));
+    }
+
+    pub fn confirm_buffer_selection(&mut self) {
+        if let Some(i) = self.buffer_list_state.selected() {
+            self.switch_buffer(i);
+        }
+        self.show_buffer_list = false;
+    }
+
+    fn set_markdown(&mut self, markdown: String) {
+        self.markdown = markdown;
+        self.revision += 1;
+    }
+
+    /// Point the watcher at the open file and the loaded tree directories.
+    fn sync_watches(&mut self) {
+        if let Some(watcher) = &mut self.watcher {
+            watcher.sync(self.tree.loaded_dirs(), self.current_file.as_deref());
+        }
+    }
+
+    /// Apply pending filesystem changes. Returns `true` if anything needs redrawing.
+    pub fn process_file_changes(&mut self) -> bool {
+        let Some(changes) = self.watcher.as_mut().map(|w| w.poll()) else {
+            return false;
+        };
+
+        if changes.file_changed && self.reload_current_file() {
+            self.show_info("File reloaded".to_string());
+        }
+        if changes.tree_changed {
+            self.refresh_tree();
+        }
+        let changed = changes.file_changed || changes.tree_changed;
+        // A checkout changes files, so this catches branch switches.
+        if changed {
+            self.update_git_branch();
+        }
+        changed
+    }
+
+    /// Re-read the open file, keeping the scroll position. Returns `false`
+    /// if it could not be read.
+    pub fn reload_current_file(&mut self) -> bool {
+        let Some(path) = &self.current_file else {
+            return false;
+        };
+        // A file that is briefly missing mid-save keeps its last content.
+        match fs::read_to_string(path) {
+            Ok(markdown) => {
+                self.set_markdown(markdown);
+                self.focused_link = None;
+                true
             }
+            Err(_) => false,
+        }
+    }
+
+    /// Re-read the tree from disk, keeping the selection on the same path.
+    fn refresh_tree(&mut self) {
+        let selected_path = self.tree.node_at(self.selected).map(|n| n.path.clone());
+        if self.tree.refresh().is_err() {
+            return;
+        }
+        self.selected = selected_path
+            .and_then(|p| self.tree.row_of(&p))
+            .unwrap_or(self.selected)
+            .min(self.tree.len().saturating_sub(1));
+        self.sync_watches();
+    }
+
+    /// Expand the selected directory (`l` in the tree).
+    pub fn expand_selected(&mut self) {
+        if self.tree.expand(self.selected) {
+            self.sync_watches();
+        }
+    }
+
+    /// Collapse the selected directory, or jump to the parent directory (`h` in the tree).
+    pub fn collapse_selected(&mut self) {
+        if let Some(row) = self.tree.collapse(self.selected) {
+            self.selected = row;
         }
-        self.last_key = None;
     }
 
     pub fn update_max_scroll(&mut self, line_count: u16, viewport_height: u16) {
+        self.viewport_height = viewport_height;
+        self.line_count = line_count as usize;
         self.max_scroll = line_count.saturating_sub(viewport_height);
+        self.scroll_offset = self.scroll_offset.min(self.max_scroll);
+        self.clamp_cursor_to_view();
     }
 
     /// Select a file by index (for mouse clicks)
     pub fn select_file_by_index(&mut self, index: usize) {
-        if index < self.files.len() {
+        if index < self.tree.len() {
             self.selected = index;
-            self.last_key = None;
         }
     }
 
-    /// Get the file index at a given row position in the tree view
+    /// Get the visible node index at a given row position in the tree view
     pub fn get_file_index_at_row(&self, row: u16, tree_start_row: u16) -> Option<usize> {
         if row < tree_start_row {
             return None;
         }
-        let index = (row - tree_start_row) as usize;
-        if index < self.files.len() {
+        let index = self.tree_state.offset() + (row - tree_start_row) as usize;
+        if index < self.tree.len() {
             Some(index)
         } else {
             None
@@ -262,17 +944,17 @@ impl App {
         // Check if clicking on a file
         if let Some(index) = self.get_file_index_at_row(row, tree_start_row) {
             // Check for double-click (within 500ms and same file)
-            if let Some(last_time) = self.last_click_time {
-                if let Some(last_file) = self.last_clicked_file {
-                    if last_file == index && current_time_ms.saturating_sub(last_time) < 500 {
-                        // Double-click detected
-                        self.select_file_by_index(index);
-                        self.open_selected_file();
-                        self.last_click_time = None;
-                        self.last_clicked_file = None;
-                        return true; // File was opened
-                    }
-                }
+            if let Some(last_time) = self.last_click_time
+                && let Some(last_file) = self.last_clicked_file
+                && last_file == index
+                && current_time_ms.saturating_sub(last_time) < 500
+            {
+                // Double-click detected
+                self.select_file_by_index(index);
+                self.open_selected_file();
+                self.last_click_time = None;
+                self.last_clicked_file = None;
+                return true; // File was opened
             }
 
             // Single click - select file
@@ -335,15 +1017,242 @@ impl App {
     }
 
     pub fn confirm_theme_selection(&mut self) {
-        if let Some(i) = self.theme_list_state.selected() {
-            if let Some(theme) = self.available_themes.get(i) {
-                self.current_theme = theme.clone();
-            }
+        if let Some(i) = self.theme_list_state.selected()
+            && let Some(theme) = self.available_themes.get(i)
+        {
+            self.current_theme = theme.clone();
         }
         self.show_theme_list = false;
     }
 
+    /// Open the fuzzy finder over every markdown file under the tree root (Ctrl+p).
+    pub fn open_finder(&mut self) {
+        self.finder = Some(Finder::new(&self.tree.root));
+    }
+
+    pub fn close_finder(&mut self) {
+        self.finder = None;
+    }
+
+    pub fn finder_input(&mut self, c: char) {
+        if let Some(finder) = &mut self.finder {
+            finder.push(c);
+        }
+    }
+
+    pub fn finder_backspace(&mut self) {
+        if let Some(finder) = &mut self.finder {
+            finder.backspace();
+        }
+    }
+
+    pub fn finder_select(&mut self, next: bool) {
+        if let Some(finder) = &mut self.finder {
+            if next {
+                finder.select_next();
+            } else {
+                finder.select_prev();
+            }
+        }
+    }
+
+    /// Select the chosen file in the tree, expanding its directories, and
+    /// open it like Enter in the tree.
+    pub fn confirm_finder(&mut self) {
+        let Some(path) = self
+            .finder
+            .take()
+            .and_then(|f| f.selected_path().map(Path::to_path_buf))
+        else {
+            return;
+        };
+        self.open_in_tree(&path);
+    }
+
+    /// Open the project search popup (Ctrl+g), optionally with a query.
+    pub fn open_project_search(&mut self, query: Option<String>) {
+        let mut search = ProjectSearch::new(&self.tree.root);
+        if let Some(query) = query {
+            search.query = query;
+            search.update_results();
+        }
+        self.project_search = Some(search);
+    }
+
+    pub fn close_project_search(&mut self) {
+        self.project_search = None;
+    }
+
+    pub fn project_search_input(&mut self, c: char) {
+        if let Some(search) = &mut self.project_search {
+            search.push(c);
+        }
+    }
+
+    pub fn project_search_backspace(&mut self) {
+        if let Some(search) = &mut self.project_search {
+            search.backspace();
+        }
+    }
+
+    pub fn project_search_select(&mut self, next: bool) {
+        if let Some(search) = &mut self.project_search {
+            if next {
+                search.select_next();
+            } else {
+                search.select_prev();
+            }
+        }
+    }
+
+    /// Open the selected result's file and move the cursor to the rendered
+    /// line of the match, highlighting the query like a `/` search.
+    pub fn confirm_project_search(&mut self) {
+        let Some(project_search) = self.project_search.take() else {
+            return;
+        };
+        let Some((path, offset)) = project_search.selected() else {
+            return;
+        };
+        self.open_in_tree(path);
+        // The buffer may have been opened before under another spelling.
+        if self.current_file.as_deref().map(normalize_path) != Some(normalize_path(path)) {
+            return;
+        }
+        self.reveal_source(offset);
+
+        if let Ok(mut search) = Search::new(&project_search.query, true) {
+            if let Some(rendered) = self.rendered() {
+                search.refresh(&rendered.text);
+            }
+            search.select_from(self.cursor_line);
+            self.search = Some(search);
+        }
+    }
+
+    /// Select `path` in the tree, expanding its directories, and open it
+    /// like Enter in the tree.
+    fn open_in_tree(&mut self, path: &Path) {
+        match self.tree.reveal(path) {
+            Some(row) => {
+                self.selected = row;
+                self.open_selected_file();
+            }
+            None => self.open_file(path),
+        }
+        self.sync_watches();
+    }
+
+    /// Move the cursor to the rendered line showing source byte `offset`.
+    fn reveal_source(&mut self, offset: usize) {
+        self.reveal_mapped(|source_map, _| source_map.line_of(offset));
+    }
+
+    /// Move the cursor to the first rendered line showing source line `line`
+    /// (counted from 1), as for `--line` and `:line`.
+    pub fn goto_source_line(&mut self, line: usize) {
+        self.reveal_mapped(|source_map, md| source_map.line_of_source_line(md, line));
+    }
+
+    /// Move the cursor to the rendered line `find` picks from the source map.
+    fn reveal_mapped(&mut self, find: impl FnOnce(&SourceMap, &str) -> Option<usize>) {
+        self.ensure_rendered();
+        let Some(rendered) = self.rendered() else {
+            return;
+        };
+        let line_count = rendered.text.lines.len() as u16;
+        let Some(line) = find(&rendered.source_map, &self.markdown) else {
+            return;
+        };
+        // The scroll limit is still the previous document's until the next draw.
+        self.update_max_scroll(line_count, self.viewport_height);
+        self.reveal_line(line);
+    }
+
+    /// Index of the heading whose section contains the top of the preview.
+    pub fn current_heading(&self) -> Option<usize> {
+        let top = self.scroll_offset as usize;
+        self.headings.iter().rposition(|h| h.line <= top)
+    }
+
+    pub fn toggle_outline(&mut self) {
+        self.show_outline = !self.show_outline;
+        if self.show_outline {
+            self.outline_state
+                .select(Some(self.current_heading().unwrap_or(0)));
+        }
+    }
+
+    pub fn next_outline_selection(&mut self) {
+        if self.headings.is_empty() {
+            return;
+        }
+        let i = match self.outline_state.selected() {
+            Some(i) if i + 1 < self.headings.len() => i + 1,
+            _ => 0,
+        };
+        self.outline_state.select(Some(i));
+    }
+
+    pub fn prev_outline_selection(&mut self) {
+        if self.headings.is_empty() {
+            return;
+        }
+        let i = match self.outline_state.selected() {
+            Some(0) | None => self.headings.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.outline_state.select(Some(i));
+    }
+
+    pub fn confirm_outline_selection(&mut self) {
+        if let Some(heading) = self
+            .outline_state
+            .selected()
+            .and_then(|i| self.headings.get(i))
+        {
+            self.scroll_offset = (heading.line as u16).min(self.max_scroll);
+            self.cursor_line = heading.line;
+            self.focus_preview();
+        }
+        self.show_outline = false;
+    }
+
+    /// Move to the `count`th heading below the cursor and scroll it to the
+    /// top of the preview (`]]`).
+    pub fn next_heading(&mut self, count: usize) {
+        let cursor = self.cursor_line;
+        if let Some(line) = self
+            .headings
+            .iter()
+            .filter(|h| h.line > cursor)
+            .nth(count - 1)
+            .map(|h| h.line)
+        {
+            self.scroll_offset = (line as u16).min(self.max_scroll);
+            self.cursor_line = line;
+        }
+    }
+
+    /// Move to the `count`th heading above the cursor (`[[`).
+    pub fn prev_heading(&mut self, count: usize) {
+        let cursor = self.cursor_line;
+        if let Some(line) = self
+            .headings
+            .iter()
+            .rev()
+            .filter(|h| h.line < cursor)
+            .nth(count - 1)
+            .map(|h| h.line)
+        {
+            self.scroll_offset = (line as u16).min(self.max_scroll);
+            self.cursor_line = line;
+        }
+    }
+
+    /// Start a selection at a position relative to the preview's inner area.
     pub fn start_selection(&mut self, col: u16, row: u16) {
+        let row = row.saturating_add(self.scroll_offset);
         self.selection = Some(SelectionRange {
             start: (col, row),
             end: (col, row),
@@ -352,7 +1261,7 @@ impl App {
 
     pub fn update_selection(&mut self, col: u16, row: u16) {
         if let Some(mut sel) = self.selection {
-            sel.end = (col, row);
+            sel.end = (col, row.saturating_add(self.scroll_offset));
             self.selection = Some(sel);
         }
     }
@@ -361,11 +1270,696 @@ impl App {
         self.selection = None;
     }
 
-    pub fn copy_selection(&mut self) {
-        if let Some(cb) = &mut self.clipboard {
-            if self.selection.is_some() {
-                let _ = cb.set_text("Selection copied! (Placeholder)");
+    /// Copy the selected text to the clipboard, either as displayed in the
+    /// preview or as the markdown source of the selected lines.
+    pub fn copy_selection(&mut self, as_source: bool) {
+        let Some(sel) = self.selection else {
+            return;
+        };
+        self.ensure_rendered();
+        let Some(rendered) = self.rendered() else {
+            return;
+        };
+        let text = if as_source {
+            selected_source(&self.markdown, &rendered.source_map, sel)
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            selected_text(&rendered.text, sel)
+        };
+
+        let chars = text.chars().count();
+        match &mut self.clipboard {
+            Some(cb) => match cb.set_text(text) {
+                Ok(()) => self.show_info(format!("Copied {} chars", chars)),
+                Err(e) => self.show_error(format!("Can't copy: {}", e)),
+            },
+            None => self.show_error("Clipboard unavailable".to_string()),
+        }
+    }
+
+    /// Re-render the document if it, the theme or the preview width changed
+    /// since the cached render.
+    pub fn ensure_rendered(&mut self) {
+        let key = (
+            self.revision,
+            self.current_theme.clone(),
+            self.last_preview_area.width,
+            self.unfold,
+        );
+        if self.render_cache.as_ref().is_some_and(|c| c.key == key) {
+            return;
+        }
+
+        let rendered = markdown_to_ratatui(
+            &self.markdown,
+            &self.current_theme,
+            &self.config.markdown,
+            self.last_preview_area,
+            self.unfold,
+        );
+        self.headings = rendered.headings.clone();
+        self.links = rendered.links.clone();
+        if let Some(search) = &mut self.search {
+            search.refresh(&rendered.text);
+        }
+        self.render_cache = Some(RenderCache { key, rendered });
+    }
+
+    /// The cached render, if [`App::ensure_rendered`] has run.
+    pub fn rendered(&self) -> Option<&Rendered> {
+        self.render_cache.as_ref().map(|c| &c.rendered)
+    }
+
+    /// Open or close all collapsed callouts (`zi`).
+    pub fn toggle_folds(&mut self) {
+        self.unfold = !self.unfold;
+    }
+
+    /// Show the raw markdown next to the preview, then instead of it, then
+    /// hide it again (`s`).
+    pub fn toggle_source_view(&mut self) {
+        self.source_view = match self.source_view {
+            SourceView::Hidden => SourceView::Split,
+            SourceView::Split => SourceView::Only,
+            SourceView::Only => SourceView::Hidden,
+        };
+    }
+
+    /// Whether screen column `column` is over the preview rather than the
+    /// source view, so mouse selection applies.
+    pub fn in_preview(&self, column: u16) -> bool {
+        self.source_view != SourceView::Only && column < self.last_preview_area.right()
+    }
+
+    /// The highlighted markdown source, redone when it or the theme changes.
+    pub fn source_lines(&mut self) -> &[Line<'static>] {
+        let fresh = matches!(&self.source_cache,
+            Some((revision, theme, _)) if *revision == self.revision && *theme == self.current_theme);
+        if !fresh {
+            let lines = highlight_source(&self.markdown, &self.current_theme);
+            self.source_cache = Some((self.revision, self.current_theme.clone(), lines));
+        }
+        self.source_cache
+            .as_ref()
+            .map_or(&[], |(_, _, lines)| lines.as_slice())
+    }
+
+    /// Source lines (counted from 0) shown on the cursor line, and the first
+    /// source line to show so that they sit level with it.
+    pub fn source_view_position(&self) -> (Range<usize>, usize) {
+        let Some(rendered) = self.rendered() else {
+            return (0..0, 0);
+        };
+        let md = &self.markdown;
+        let cursor = match rendered.source_map.source_of(self.cursor_line) {
+            Some(source) => {
+                let last = source.end.saturating_sub(1).max(source.start);
+                line_index(md, source.start)..line_index(md, last) + 1
+            }
+            // A blank line: line up the next source line instead.
+            None => {
+                let next = (rendered.source_map)
+                    .source_line_at(md, self.cursor_line)
+                    .map_or(0, |line| line - 1);
+                next..next
+            }
+        };
+        let row = self.cursor_line.saturating_sub(self.scroll_offset as usize);
+        let top = cursor.start.saturating_sub(row);
+        (cursor, top)
+    }
+
+    /// Move the cursor to `line` and scroll it into view, centering it if it
+    /// was off-screen.
+    fn reveal_line(&mut self, line: usize) {
+        self.cursor_line = line;
+        let line = line.min(u16::MAX as usize) as u16;
+        let top = self.scroll_offset;
+        let bottom = top.saturating_add(self.viewport_height);
+        if line < top || line >= bottom {
+            self.scroll_offset = line
+                .saturating_sub(self.viewport_height / 2)
+                .min(self.max_scroll);
+        }
+    }
+
+    /// Open the search prompt (`/` forward, `?` backward).
+    pub fn start_search(&mut self, forward: bool) {
+        self.focus_preview();
+        self.search_prompt = Some(SearchPrompt {
+            input: String::new(),
+            forward,
+            origin: self.scroll_offset,
+        });
+        self.status_message = None;
+    }
+
+    pub fn search_input(&mut self, c: char) {
+        if let Some(prompt) = &mut self.search_prompt {
+            prompt.input.push(c);
+            self.update_incremental_search();
+        }
+    }
+
+    pub fn search_backspace(&mut self) {
+        let Some(prompt) = &mut self.search_prompt else {
+            return;
+        };
+        if prompt.input.pop().is_none() {
+            self.cancel_search();
+        } else {
+            self.update_incremental_search();
+        }
+    }
+
+    /// Re-run the search for the text typed so far and jump to the first match.
+    fn update_incremental_search(&mut self) {
+        let Some(prompt) = &self.search_prompt else {
+            return;
+        };
+        self.scroll_offset = prompt.origin;
+        self.status_message = None;
+        if prompt.input.is_empty() {
+            self.search = None;
+            return;
+        }
+
+        // Patterns that don't compile yet (e.g. a lone `(`) just clear the highlights.
+        let search = Search::new(&prompt.input, prompt.forward).ok();
+        let origin = prompt.origin as usize;
+        self.ensure_rendered();
+        self.search = search;
+        if let (Some(search), Some(rendered)) = (&mut self.search, &self.render_cache) {
+            search.refresh(&rendered.rendered.text);
+            if let Some(m) = search.select_from(origin) {
+                self.reveal_line(m.line);
             }
         }
     }
+
+    pub fn confirm_search(&mut self) {
+        let Some(prompt) = self.search_prompt.take() else {
+            return;
+        };
+        if prompt.input.is_empty() {
+            return;
+        }
+
+        match Search::new(&prompt.input, prompt.forward) {
+            Ok(mut search) => {
+                self.ensure_rendered();
+                if let Some(rendered) = self.rendered() {
+                    search.refresh(&rendered.text);
+                }
+                match search.select_from(prompt.origin as usize) {
+                    Some(m) => self.reveal_line(m.line),
+                    None => self.show_error(format!("Pattern not found: {}", prompt.input)),
+                }
+                self.search = Some(search);
+            }
+            Err(e) => {
+                self.scroll_offset = prompt.origin;
+                self.search = None;
+                self.show_error(format!("Invalid pattern: {}", e));
+            }
+        }
+    }
+
+    pub fn cancel_search(&mut self) {
+        if let Some(prompt) = self.search_prompt.take() {
+            self.scroll_offset = prompt.origin;
+        }
+        self.search = None;
+    }
+
+    /// Jump to the next match (`n`), or the previous one with `reverse` (`N`).
+    pub fn search_next(&mut self, reverse: bool) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let found: Option<SearchMatch> = search.step(reverse);
+        match found {
+            Some(m) => {
+                self.status_message = None;
+                self.reveal_line(m.line);
+            }
+            None => {
+                let message = format!("Pattern not found: {}", search.pattern);
+                self.show_error(message);
+            }
+        }
+    }
+
+    /// Remove search highlighting.
+    pub fn clear_search(&mut self) {
+        self.search = None;
+        self.status_message = None;
+    }
+
+    /// Open the `:` prompt.
+    pub fn start_command(&mut self) {
+        self.command_prompt = Some(CommandPrompt::default());
+        self.status_message = None;
+    }
+
+    pub fn command_input(&mut self, c: char) {
+        if let Some(prompt) = &mut self.command_prompt {
+            prompt.push(c);
+        }
+    }
+
+    pub fn command_backspace(&mut self) {
+        if let Some(prompt) = &mut self.command_prompt
+            && !prompt.backspace()
+        {
+            self.command_prompt = None;
+        }
+    }
+
+    /// Complete the command, file path, theme or option being typed (Tab).
+    pub fn complete_command(&mut self, reverse: bool) {
+        if let Some(prompt) = &mut self.command_prompt {
+            prompt.complete(&self.available_themes, reverse);
+        }
+    }
+
+    /// Recall an older (Up) or newer (Down) command from the history.
+    pub fn browse_command_history(&mut self, older: bool) {
+        if let Some(prompt) = &mut self.command_prompt {
+            prompt.browse_history(&self.command_history, older);
+        }
+    }
+
+    pub fn cancel_command(&mut self) {
+        self.command_prompt = None;
+    }
+
+    /// Run the command typed at the `:` prompt. Returns `false` when mdvim
+    /// should quit.
+    pub fn confirm_command(&mut self) -> bool {
+        let Some(prompt) = self.command_prompt.take() else {
+            return true;
+        };
+        command::remember(&mut self.command_history, &prompt.input);
+        match command::parse(&prompt.input) {
+            Ok(Some(command)) => self.run_command(command),
+            Ok(None) => true,
+            Err(e) => {
+                self.show_error(e);
+                true
+            }
+        }
+    }
+
+    fn run_command(&mut self, command: ExCommand) -> bool {
+        match command {
+            ExCommand::Edit(Some(path)) => {
+                if !path.is_file() {
+                    self.show_error(format!("Can't open file: {}", path.display()));
+                    return true;
+                }
+                self.open_file(&path);
+                if let Some(row) = self.tree.row_of(&path) {
+                    self.selected = row;
+                }
+            }
+            ExCommand::Edit(None) if self.current_file.is_some() => {
+                if self.reload_current_file() {
+                    self.show_info("File reloaded".to_string());
+                } else if let Some(path) = &self.current_file {
+                    let message = format!("Can't read {}", path.display());
+                    self.show_error(message);
+                }
+            }
+            ExCommand::Edit(None) => self.show_error("No file name".to_string()),
+            ExCommand::Theme(Some(name)) => {
+                match self
+                    .available_themes
+                    .iter()
+                    .find(|t| t.eq_ignore_ascii_case(&name))
+                {
+                    Some(theme) => self.current_theme = theme.clone(),
+                    None => self.show_error(format!("Unknown theme: {}", name)),
+                }
+            }
+            ExCommand::Theme(None) => self.toggle_theme_list(),
+            ExCommand::Set(SetOption::TreeWidth(width)) => self.tree_width_percentage = width,
+            ExCommand::Set(SetOption::Tree(show)) => {
+                if show != self.show_tree {
+                    self.toggle_tree();
+                }
+            }
+            ExCommand::Grep(query) => self.open_project_search(query),
+            ExCommand::Goto(line) => {
+                self.focus_preview();
+                self.goto_line(line);
+            }
+            ExCommand::Line(line) => {
+                self.focus_preview();
+                self.goto_source_line(line);
+            }
+            ExCommand::Write { path, force } => self.export_html(&path, force),
+            ExCommand::Buffer(Some(n)) if n > self.buffers.len() => {
+                self.show_error(format!("Buffer {} does not exist", n));
+            }
+            ExCommand::Buffer(Some(n)) => self.switch_buffer(n - 1),
+            ExCommand::Buffer(None) => self.toggle_buffer_list(),
+            ExCommand::BufferNext => self.next_buffer(1),
+            ExCommand::BufferPrev => self.prev_buffer(1),
+            ExCommand::BufferDelete => self.close_buffer(),
+            ExCommand::Quit => return false,
+        }
+        true
+    }
+
+    /// Write the document as HTML (`:w`). An existing file is only replaced
+    /// with `force` (`:w!`).
+    fn export_html(&mut self, path: &Path, force: bool) {
+        if path.exists() && !force {
+            self.show_error(format!(
+                "File exists (add ! to override): {}",
+                path.display()
+            ));
+            return;
+        }
+        let title = self
+            .current_file
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map_or("mdvim".into(), |stem| stem.to_string_lossy());
+        let html = markdown_to_html(&self.markdown, &title);
+        match fs::write(path, &html) {
+            Ok(()) => self.show_info(format!("\"{}\" {}B written", path.display(), html.len())),
+            Err(e) => self.show_error(format!("Can't write {}: {}", path.display(), e)),
+        }
+    }
+
+    fn show_error(&mut self, text: String) {
+        self.status_message = Some(StatusMessage {
+            text,
+            is_error: true,
+        });
+    }
+
+    fn show_info(&mut self, text: String) {
+        self.status_message = Some(StatusMessage {
+            text,
+            is_error: false,
+        });
+    }
+
+    /// Focus the next link (Tab), or the previous one with `reverse` (Shift+Tab).
+    /// Without a focused link, starts from the top of the preview.
+    pub fn cycle_link(&mut self, reverse: bool) {
+        let len = self.links.len();
+        if len == 0 {
+            return;
+        }
+        let top = self.scroll_offset as usize;
+        let index = match (self.focused_link, reverse) {
+            (Some(i), false) => (i + 1) % len,
+            (Some(i), true) => (i + len - 1) % len,
+            (None, false) => self.links.iter().position(|l| l.line >= top).unwrap_or(0),
+            (None, true) => self
+                .links
+                .iter()
+                .rposition(|l| l.line < top + self.viewport_height as usize)
+                .unwrap_or(len - 1),
+        };
+        self.focused_link = Some(index);
+        self.focus_preview();
+        self.reveal_line(self.links[index].line);
+    }
+
+    /// Follow the focused link: anchors and markdown files open in mdvim,
+    /// everything else goes to the external opener.
+    pub fn follow_link(&mut self) {
+        let Some(link) = self.focused_link.and_then(|i| self.links.get(i)).cloned() else {
+            return;
+        };
+
+        if let Some(anchor) = link.url.strip_prefix('#') {
+            self.push_history();
+            self.jump_to_anchor(anchor);
+            return;
+        }
+        if is_external_url(&link.url) {
+            self.open_external(&link.url);
+            return;
+        }
+
+        let (target, fragment) = match link.url.split_once('#') {
+            Some((target, fragment)) => (target, Some(fragment)),
+            None => (link.url.as_str(), None),
+        };
+        let target = target.replace("%20", " ");
+        let base = self
+            .current_file
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(&self.tree.root)
+            .to_path_buf();
+        let candidates = if link.wiki {
+            vec![
+                base.join(format!("{}.md", target)),
+                base.join(&target),
+                self.tree.root.join(format!("{}.md", target)),
+            ]
+        } else {
+            vec![base.join(&target)]
+        };
+
+        let Some(path) = candidates.into_iter().find(|p| p.is_file()) else {
+            self.show_error(format!("Link target not found: {}", link.url));
+            return;
+        };
+        if !is_markdown_file(&path) {
+            self.open_external(&path.to_string_lossy());
+            return;
+        }
+
+        self.open_file(&path);
+        if let Some(fragment) = fragment {
+            self.jump_to_anchor(fragment);
+        }
+    }
+
+    /// Scroll to the heading or footnote with the given anchor in the
+    /// current document.
+    fn jump_to_anchor(&mut self, anchor: &str) {
+        // Headings may not have been drawn yet for a freshly opened file.
+        self.ensure_rendered();
+        let line = self
+            .headings
+            .iter()
+            .find(|h| h.anchor.eq_ignore_ascii_case(anchor))
+            .map(|h| h.line)
+            .or_else(|| {
+                self.rendered()?
+                    .footnotes
+                    .iter()
+                    .find(|f| f.anchor.eq_ignore_ascii_case(anchor))?
+                    .definition
+                    .as_ref()
+                    .map(|lines| lines.start)
+            });
+        match line {
+            Some(line) => {
+                self.scroll_offset = line as u16;
+                self.cursor_line = line;
+            }
+            None => self.show_error(format!("Anchor not found: #{}", anchor)),
+        }
+    }
+
+    /// Jump from a footnote reference on the cursor line to its definition,
+    /// or from a definition back to the reference it was reached from (`gd`).
+    /// On a definition that has references itself, a focused one goes forward.
+    pub fn jump_footnote(&mut self) {
+        self.ensure_rendered();
+        let Some(rendered) = self.rendered() else {
+            return;
+        };
+        let line = self.cursor_line;
+        let urls: Vec<String> = (rendered.footnotes.iter())
+            .map(|f| format!("#{}", f.anchor))
+            .collect();
+        let definition = (rendered.footnotes.iter().zip(&urls))
+            .find(|(f, _)| f.definition.as_ref().is_some_and(|d| d.contains(&line)))
+            .map(|(_, url)| url.clone());
+
+        let is_reference = |k: &&LinkEntry| k.line == line && urls.contains(&k.url);
+        let focused = self.focused_link.and_then(|i| self.links.get(i));
+        let reference = match definition {
+            Some(_) => focused.filter(is_reference),
+            None => focused
+                .filter(is_reference)
+                .or_else(|| self.links.iter().find(is_reference)),
+        };
+        if let Some(reference) = reference {
+            let url = reference.url.clone();
+            self.footnote_origin = Some((url.clone(), line));
+            self.push_history();
+            self.jump_to_anchor(&url[1..]);
+            return;
+        }
+
+        let Some(url) = definition else {
+            self.show_error("No footnote reference on this line".to_string());
+            return;
+        };
+        // Back where `gd` came from, or else to the first reference.
+        let target = match &self.footnote_origin {
+            Some((origin, l))
+                if *origin == url && self.links.iter().any(|k| k.line == *l && k.url == url) =>
+            {
+                Some(*l)
+            }
+            _ => self.links.iter().find(|k| k.url == url).map(|k| k.line),
+        };
+        match target {
+            Some(target) => {
+                self.push_history();
+                self.reveal_line(target);
+            }
+            None => self.show_error("Footnote is never referenced".to_string()),
+        }
+    }
+
+    /// Launch the opener command for a URL or non-markdown file.
+    fn open_external(&mut self, target: &str) {
+        let opener = self.opener.as_deref().unwrap_or(DEFAULT_OPENER);
+        let mut parts = opener.split_whitespace();
+        let Some(program) = parts.next() else {
+            return;
+        };
+        let spawned = Command::new(program)
+            .args(parts)
+            .arg(target)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Err(e) = spawned {
+            self.show_error(format!("Failed to run '{}': {}", program, e));
+        }
+    }
+
+    /// Ask the main loop to open the file in the editor (`e`).
+    pub fn request_edit(&mut self) {
+        if self.current_file.is_some() {
+            self.edit_requested = true;
+        } else {
+            self.show_error("No file name".to_string());
+        }
+    }
+
+    /// Run `$EDITOR +<line> file` on the source line at the top of the
+    /// preview and wait for it, then reload the file. The caller suspends
+    /// the terminal around this.
+    pub fn edit_in_editor(&mut self) {
+        let Some(path) = self.current_file.clone() else {
+            return;
+        };
+        self.ensure_rendered();
+        let line = self
+            .rendered()
+            .and_then(|r| {
+                r.source_map
+                    .source_line_at(&self.markdown, self.scroll_offset as usize)
+            })
+            .unwrap_or(1);
+        let editor = env::var("EDITOR")
+            .ok()
+            .filter(|e| !e.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+        let mut parts = editor.split_whitespace();
+        let Some(program) = parts.next() else {
+            return;
+        };
+        let status = Command::new(program)
+            .args(parts)
+            .arg(format!("+{}", line))
+            .arg(&path)
+            .status();
+        match status {
+            Ok(status) if !status.success() => {
+                self.show_error(format!("'{}' exited with {}", program, status));
+            }
+            Ok(_) => {}
+            Err(e) => self.show_error(format!("Failed to run '{}': {}", program, e)),
+        }
+        // The editor may have deleted or renamed it.
+        if !self.reload_current_file() {
+            self.show_error(format!("Can't read {}", path.display()));
+        }
+    }
+
+    fn push_history(&mut self) {
+        self.back_history.push(HistoryEntry {
+            file: self.current_file.clone(),
+            scroll_offset: self.scroll_offset,
+        });
+        self.forward_history.clear();
+    }
+
+    /// Go back to the previous position (Ctrl+o).
+    pub fn history_back(&mut self) {
+        if let Some(entry) = self.back_history.pop() {
+            let here = HistoryEntry {
+                file: self.current_file.clone(),
+                scroll_offset: self.scroll_offset,
+            };
+            self.forward_history.push(here);
+            self.restore_history_entry(entry);
+        }
+    }
+
+    /// Go forward again after going back (Ctrl+i).
+    pub fn history_forward(&mut self) {
+        if let Some(entry) = self.forward_history.pop() {
+            let here = HistoryEntry {
+                file: self.current_file.clone(),
+                scroll_offset: self.scroll_offset,
+            };
+            self.back_history.push(here);
+            self.restore_history_entry(entry);
+        }
+    }
+
+    fn restore_history_entry(&mut self, entry: HistoryEntry) {
+        if let Some(file) = &entry.file
+            && self.current_file.as_ref() != Some(file)
+        {
+            self.load_file(file);
+        }
+        self.scroll_offset = entry.scroll_offset;
+        self.cursor_line = entry.scroll_offset as usize;
+        self.focused_link = None;
+    }
+}
+
+#[cfg(target_os = "macos")]
+const DEFAULT_OPENER: &str = "open";
+#[cfg(windows)]
+const DEFAULT_OPENER: &str = "explorer";
+#[cfg(not(any(target_os = "macos", windows)))]
+const DEFAULT_OPENER: &str = "xdg-open";
+
+/// Editor used by `e` when `$EDITOR` is not set.
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+fn is_external_url(url: &str) -> bool {
+    url.contains("://") || url.starts_with("mailto:")
+}
+
+pub fn is_markdown_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("md" | "markdown" | "mdown" | "mkd")
+    )
 }
@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: mdvim [OPTIONS] [PATH]
+
+Arguments:
+  [PATH]  Markdown file to open, or directory to use as the tree root [default: .]
+
+Options:
+      --theme <NAME>       Syntax highlighting theme for code blocks
+      --no-tree            Start with the file tree hidden
+      --tree-width <PCT>   File tree width in percent of the terminal (10-80)
+      --line <N>           Start at line N of the markdown source
+      --opener <CMD>       Command used to open external links [default: xdg-open]
+  -h, --help               Print help
+  -V, --version            Print version";
+
+/// Startup options given on the command line.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub path: Option<PathBuf>,
+    pub theme: Option<String>,
+    pub no_tree: bool,
+    pub tree_width: Option<u16>,
+    pub line: Option<usize>,
+    pub opener: Option<String>,
+}
+
+/// What the command line asks mdvim to do.
+pub enum Command {
+    Run(Args),
+    Help,
+    Version,
+}
+
+/// Parse command-line arguments (without the program name).
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        // Accept both `--flag value` and `--flag=value`.
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| -> Result<String, String> {
+            inline_value
+                .map(str::to_string)
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for '{}'", name))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--no-tree" => parsed.no_tree = true,
+            "--theme" => {
+                let theme = value("--theme")?;
+                let themes = crate::syntax::get_available_themes();
+                if !themes.contains(&theme) {
+                    return Err(format!(
+                        "unknown theme '{}' (available: {})",
+                        theme,
+                        themes.join(", ")
+                    ));
+                }
+                parsed.theme = Some(theme);
+            }
+            "--tree-width" => {
+                let raw = value("--tree-width")?;
+                let width = raw
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|w| (10..=80).contains(w))
+                    .ok_or_else(|| format!("invalid tree width '{}' (expected 10 to 80)", raw))?;
+                parsed.tree_width = Some(width);
+            }
+            "--line" => {
+                let raw = value("--line")?;
+                let line = raw
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("invalid line number '{}'", raw))?;
+                parsed.line = Some(line);
+            }
+            "--opener" => {
+                let opener = value("--opener")?;
+                if opener.trim().is_empty() {
+                    return Err("the opener command must not be empty".to_string());
+                }
+                parsed.opener = Some(opener);
+            }
+            _ if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unexpected option '{}'", flag));
+            }
+            _ => {
+                if parsed.path.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                let path = PathBuf::from(&arg);
+                if !path.exists() {
+                    return Err(format!("'{}': no such file or directory", arg));
+                }
+                parsed.path = Some(path);
+            }
+        }
+    }
+
+    Ok(Command::Run(parsed))
+}
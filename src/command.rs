@@ -0,0 +1,408 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Command names; a prefix such as `e` or `se` picks the first match, so
+/// `buffer` comes first for `:b`.
+const COMMANDS: &[&str] = &[
+    "buffer",
+    "bdelete",
+    "bnext",
+    "bprevious",
+    "buffers",
+    "edit",
+    "goto",
+    "grep",
+    "line",
+    "ls",
+    "quit",
+    "set",
+    "theme",
+    "write",
+];
+
+/// Options for `:set`, as offered by completion.
+const OPTIONS: &[&str] = &["tree-width=", "tree", "notree"];
+
+/// Most commands kept in the `:` history.
+const HISTORY_LIMIT: usize = 100;
+
+/// A command typed at the `:` prompt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExCommand {
+    /// `:e path`, or `:e` alone to reload the open file.
+    Edit(Option<PathBuf>),
+    /// `:theme Name`, or `:theme` alone to open the theme picker.
+    Theme(Option<String>),
+    Set(SetOption),
+    /// `:grep pattern`: search every markdown file in the project.
+    Grep(Option<String>),
+    /// `:goto 120` or `:120`: go to a preview line.
+    Goto(usize),
+    /// `:line 42`: go to where line 42 of the markdown source is shown.
+    Line(usize),
+    /// `:w export.html`; `:w!` overwrites an existing file.
+    Write {
+        path: PathBuf,
+        force: bool,
+    },
+    /// `:b 2`: show the second buffer; `:b`, `:buffers` or `:ls` alone open
+    /// the buffer picker.
+    Buffer(Option<usize>),
+    BufferNext,
+    BufferPrev,
+    BufferDelete,
+    Quit,
+}
+
+/// A setting changed with `:set`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SetOption {
+    TreeWidth(u16),
+    Tree(bool),
+}
+
+/// Parse a command line (without the leading `:`). Blank input is `None`.
+pub fn parse(input: &str) -> Result<Option<ExCommand>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(line) = input.parse::<usize>() {
+        return Ok(Some(ExCommand::Goto(line.max(1))));
+    }
+
+    let (name, force, arg) = split(input);
+    let arg = (!arg.is_empty()).then_some(arg);
+    let command = match resolve(name) {
+        Some("edit") => ExCommand::Edit(arg.map(PathBuf::from)),
+        Some("theme") => ExCommand::Theme(arg.map(str::to_string)),
+        Some("set") => ExCommand::Set(parse_option(arg.ok_or("Argument required")?)?),
+        Some(name @ ("goto" | "line")) => {
+            let arg = arg.ok_or("Argument required")?;
+            let line = arg
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid line number: {}", arg))?
+                .max(1);
+            match name {
+                "goto" => ExCommand::Goto(line),
+                _ => ExCommand::Line(line),
+            }
+        }
+        Some("grep") => ExCommand::Grep(arg.map(str::to_string)),
+        Some("write") => ExCommand::Write {
+            path: PathBuf::from(arg.ok_or("No file name")?),
+            force,
+        },
+        Some("buffer") => match arg {
+            Some(arg) => {
+                let n = arg
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid buffer number: {}", arg))?;
+                ExCommand::Buffer(Some(n))
+            }
+            None => ExCommand::Buffer(None),
+        },
+        Some("buffers" | "ls") => ExCommand::Buffer(None),
+        Some("bnext") => ExCommand::BufferNext,
+        Some("bprevious") => ExCommand::BufferPrev,
+        Some("bdelete") => ExCommand::BufferDelete,
+        Some("quit") => ExCommand::Quit,
+        _ => return Err(format!("Not an editor command: {}", input)),
+    };
+    Ok(Some(command))
+}
+
+/// Split a command line into its name, whether it has a `!`, and the argument.
+fn split(input: &str) -> (&str, bool, &str) {
+    let end = input
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(input.len());
+    let (name, rest) = input.split_at(end);
+    let (force, rest) = match rest.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    (name, force, rest.trim_start())
+}
+
+fn resolve(name: &str) -> Option<&'static str> {
+    if name.is_empty() {
+        return None;
+    }
+    COMMANDS.iter().copied().find(|c| c.starts_with(name))
+}
+
+fn parse_option(arg: &str) -> Result<SetOption, String> {
+    match arg.split_once('=') {
+        Some(("tree-width", value)) => value
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|w| (10..=80).contains(w))
+            .map(SetOption::TreeWidth)
+            .ok_or_else(|| format!("Invalid tree width: {} (expected 10 to 80)", value)),
+        None if arg == "tree" => Ok(SetOption::Tree(true)),
+        None if arg == "notree" => Ok(SetOption::Tree(false)),
+        _ => Err(format!("Unknown option: {}", arg)),
+    }
+}
+
+/// Completions for `input`: the byte offset where the completed word starts
+/// and the candidates to put there.
+fn completions(input: &str, themes: &[String]) -> (usize, Vec<String>) {
+    let start = input.len() - input.trim_start().len();
+    let (name, _, arg) = split(&input[start..]);
+    let arg_start = input.len() - arg.len();
+
+    // Still typing the command name.
+    if input.len() == start + name.len() {
+        let candidates = COMMANDS
+            .iter()
+            .filter(|c| c.starts_with(name))
+            .map(|c| c.to_string())
+            .collect();
+        return (start, candidates);
+    }
+
+    let candidates = match resolve(name) {
+        Some("edit" | "write") => complete_path(arg),
+        Some("theme") => {
+            let arg = arg.to_lowercase();
+            themes
+                .iter()
+                .filter(|t| t.to_lowercase().starts_with(&arg))
+                .cloned()
+                .collect()
+        }
+        Some("set") => OPTIONS
+            .iter()
+            .filter(|o| o.starts_with(arg))
+            .map(|o| o.to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+    (arg_start, candidates)
+}
+
+/// Files and directories starting with `arg`, relative to the working
+/// directory. Directories end in `/`; dotfiles only match a leading `.`.
+fn complete_path(arg: &str) -> Vec<String> {
+    let (dir, prefix) = match arg.rfind('/') {
+        Some(i) => arg.split_at(i + 1),
+        None => ("", arg),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    }) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Tab completion in progress.
+pub struct Completion {
+    /// Byte offset in the input where the candidate goes.
+    start: usize,
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+/// The `:` prompt while a command is being typed.
+#[derive(Default)]
+pub struct CommandPrompt {
+    pub input: String,
+    /// Set while cycling through several completions with Tab.
+    pub completion: Option<Completion>,
+    /// History entry shown while browsing with Up / Down.
+    history_index: Option<usize>,
+    /// What was typed before browsing; only entries starting with it are shown.
+    typed: String,
+}
+
+impl CommandPrompt {
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+        self.reset();
+    }
+
+    /// Delete the last character. Returns `false` if the prompt was already empty.
+    pub fn backspace(&mut self) -> bool {
+        self.reset();
+        self.input.pop().is_some()
+    }
+
+    fn reset(&mut self) {
+        self.completion = None;
+        self.history_index = None;
+    }
+
+    /// Complete the word before the end of the input (Tab), or cycle to the
+    /// next or, with `reverse`, the previous candidate (Shift+Tab).
+    pub fn complete(&mut self, themes: &[String], reverse: bool) {
+        if let Some(completion) = &mut self.completion {
+            let len = completion.candidates.len();
+            completion.selected = if reverse {
+                (completion.selected + len - 1) % len
+            } else {
+                (completion.selected + 1) % len
+            };
+            self.input.truncate(completion.start);
+            self.input
+                .push_str(&completion.candidates[completion.selected]);
+            return;
+        }
+
+        let (start, candidates) = completions(&self.input, themes);
+        self.history_index = None;
+        let selected = if reverse {
+            candidates.len().saturating_sub(1)
+        } else {
+            0
+        };
+        let Some(candidate) = candidates.get(selected) else {
+            return;
+        };
+        self.input.truncate(start);
+        self.input.push_str(candidate);
+        // A single match is simply accepted, so Tab continues into a directory.
+        if candidates.len() > 1 {
+            self.completion = Some(Completion {
+                start,
+                candidates,
+                selected,
+            });
+        }
+    }
+
+    /// Show the previous (`older`) or next history entry that starts with
+    /// the text typed before browsing.
+    pub fn browse_history(&mut self, history: &[String], older: bool) {
+        self.completion = None;
+        if self.history_index.is_none() {
+            if !older {
+                return;
+            }
+            self.typed = self.input.clone();
+        }
+        let current = self.history_index.unwrap_or(history.len());
+        let found = if older {
+            history[..current]
+                .iter()
+                .rposition(|h| h.starts_with(&self.typed))
+        } else {
+            history[current + 1..]
+                .iter()
+                .position(|h| h.starts_with(&self.typed))
+                .map(|i| current + 1 + i)
+        };
+        match found {
+            Some(i) => {
+                self.history_index = Some(i);
+                self.input = history[i].clone();
+            }
+            // Past the newest entry: back to what was typed.
+            None if !older => {
+                self.history_index = None;
+                self.input = self.typed.clone();
+            }
+            None => {}
+        }
+    }
+}
+
+/// Add a command to the history, moving it to the end if it was already there.
+pub fn remember(history: &mut Vec<String>, command: &str) {
+    let command = command.trim();
+    if command.is_empty() {
+        return;
+    }
+    history.retain(|h| h != command);
+    history.push(command.to_string());
+    if history.len() > HISTORY_LIMIT {
+        history.remove(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(input: &str) -> ExCommand {
+        parse(input).unwrap().unwrap()
+    }
+
+    #[test]
+    fn blank_input_is_no_command() {
+        assert_eq!(parse("   "), Ok(None));
+    }
+
+    #[test]
+    fn prefixes_pick_the_first_command() {
+        assert_eq!(ok("e notes.md"), ExCommand::Edit(Some("notes.md".into())));
+        assert_eq!(ok("e"), ExCommand::Edit(None));
+        assert_eq!(ok("se notree"), ExCommand::Set(SetOption::Tree(false)));
+        assert_eq!(ok("b"), ExCommand::Buffer(None));
+        assert_eq!(ok("b 2"), ExCommand::Buffer(Some(2)));
+        assert_eq!(ok("bn"), ExCommand::BufferNext);
+        assert_eq!(ok("q"), ExCommand::Quit);
+        assert_eq!(ok("ls"), ExCommand::Buffer(None));
+    }
+
+    #[test]
+    fn numbers_go_to_lines() {
+        assert_eq!(ok("120"), ExCommand::Goto(120));
+        assert_eq!(ok("0"), ExCommand::Goto(1));
+        assert_eq!(ok("goto 5"), ExCommand::Goto(5));
+        assert_eq!(ok("line 42"), ExCommand::Line(42));
+        assert_eq!(parse("line x"), Err("Invalid line number: x".to_string()));
+        assert_eq!(parse("line"), Err("Argument required".to_string()));
+    }
+
+    #[test]
+    fn write_takes_a_bang() {
+        let write = |path: &str, force| ExCommand::Write {
+            path: path.into(),
+            force,
+        };
+        assert_eq!(ok("w out.html"), write("out.html", false));
+        assert_eq!(ok("w! out.html"), write("out.html", true));
+        assert_eq!(parse("w"), Err("No file name".to_string()));
+    }
+
+    #[test]
+    fn set_options() {
+        assert_eq!(
+            ok("set tree-width=30"),
+            ExCommand::Set(SetOption::TreeWidth(30))
+        );
+        assert_eq!(ok("set notree"), ExCommand::Set(SetOption::Tree(false)));
+        assert!(parse("set tree-width=5").is_err());
+        assert_eq!(parse("set wrap"), Err("Unknown option: wrap".to_string()));
+    }
+
+    #[test]
+    fn unknown_commands_are_errors() {
+        assert_eq!(
+            parse("frobnicate"),
+            Err("Not an editor command: frobnicate".to_string())
+        );
+        assert!(parse("b 0").is_err());
+    }
+}
@@ -0,0 +1,485 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use toml::Table;
+
+use crate::keymap::Keymap;
+
+/// The built-in configuration. A user config is merged over it key by key, so
+/// it only needs to mention what it changes; this is also the reference for
+/// every available setting.
+pub const DEFAULT_CONFIG: &str = r##"
+# Syntax highlighting theme for code blocks.
+theme = "TokyoNight"
+# Command used to open external links (defaults to xdg-open / open / explorer).
+# opener = "firefox"
+
+[tree]
+visible = true
+# Width in percent of the terminal (10-80).
+width = 20
+
+[scroll]
+line = 1        # j / k
+half-page = 0   # Ctrl+d / Ctrl+u; 0 is half the preview height
+page = 0        # PageDown / PageUp; 0 is the preview height less 2 lines
+mouse = 3       # mouse wheel
+
+# Colors are names ("white", "dark-gray", "light-cyan"), "#rrggbb" or a
+# 256-color index ("208").
+[colors]
+tree-border = "#87cefa"
+preview-border = "#ffb6c1"
+# Focused pane border, tree cursor and prompt cursor.
+accent = "#ff69b4"
+popup-border = "yellow"
+popup-current = "green"
+popup-selected = "dark-gray"
+tree-guide = "dark-gray"
+selection = "white"
+cursor-line = "#303040"
+search-match = "#ffdab9"
+search-current = "#ff69b4"
+status = "#87cefa"
+error = "#dc5050"
+
+# Styles take fg, bg and the flags bold, italic, underlined, dim and
+# crossed-out.
+[markdown]
+text = { fg = "white" }
+h1 = { fg = "white", bg = "#4169e1", bold = true }
+h2 = { fg = "#87cefa", bold = true }
+h3 = { fg = "#ffb6c1", bold = true }
+h4 = { fg = "#90ee90", bold = true }
+h5 = { fg = "#dda0dd", bold = true }
+h6 = { fg = "#ffdab9", bold = true }
+h1-underline = { fg = "#87cefa" }
+strong = { fg = "#ffdab9", bold = true }
+emphasis = { fg = "#dda0dd", italic = true }
+strikethrough = { fg = "#a9a9a9", crossed-out = true }
+code = { fg = "#dc5050", bg = "#3c3c3c", bold = true }
+code-block = { fg = "white" }
+code-border = { fg = "dark-gray" }
+code-language = { fg = "#ffb6c1", italic = true }
+link = { fg = "#87cefa", dim = true }
+image = { fg = "#ffb6c1" }
+quote = { fg = "#ffb6c1", bold = true }
+# Alerts (`> [!NOTE]`, ...) and callouts (`> [!info]`).
+note = { fg = "#4493f8", bold = true }
+tip = { fg = "#3fb950", bold = true }
+important = { fg = "#ab7df8", bold = true }
+warning = { fg = "#d29922", bold = true }
+caution = { fg = "#f85149", bold = true }
+rule = { fg = "#ffb6c1" }
+math = { fg = "#90ee90", italic = true }
+footnote = { fg = "light-cyan", dim = true }
+table-border = { fg = "#87cefa" }
+table-header = { fg = "#ffb6c1", bold = true }
+task-done = { fg = "light-green" }
+task-open = { fg = "dark-gray" }
+# Bullet and number colors, cycled by list depth.
+bullets = ["#ffb6c1", "#add8e6", "#ffdab9"]
+
+# Extra key bindings per context, e.g. "<C-f>" = "page-down" under
+# [keys.preview]. Contexts are global, tree, preview and popup; see the README
+# for key notation and action names. Bind a key to "nop" to disable it.
+[keys]
+# Milliseconds to wait for the next key of a sequence like gg.
+timeout = 1000
+"##;
+
+/// Settings read from `config.toml`, with defaults for anything not set.
+#[derive(Debug)]
+pub struct Config {
+    pub theme: String,
+    pub opener: Option<String>,
+    pub tree: TreeConfig,
+    pub scroll: ScrollConfig,
+    pub colors: Palette,
+    pub markdown: MarkdownStyles,
+    pub keymap: Keymap,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TreeConfig {
+    pub visible: bool,
+    pub width: u16,
+}
+
+/// Lines moved by each kind of scroll.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ScrollConfig {
+    pub line: u16,
+    pub half_page: u16,
+    pub page: u16,
+    pub mouse: u16,
+}
+
+/// Colors of the interface around the document.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Palette {
+    #[serde(deserialize_with = "color")]
+    pub tree_border: Color,
+    #[serde(deserialize_with = "color")]
+    pub preview_border: Color,
+    #[serde(deserialize_with = "color")]
+    pub accent: Color,
+    #[serde(deserialize_with = "color")]
+    pub popup_border: Color,
+    #[serde(deserialize_with = "color")]
+    pub popup_current: Color,
+    #[serde(deserialize_with = "color")]
+    pub popup_selected: Color,
+    #[serde(deserialize_with = "color")]
+    pub tree_guide: Color,
+    #[serde(deserialize_with = "color")]
+    pub selection: Color,
+    #[serde(deserialize_with = "color")]
+    pub cursor_line: Color,
+    #[serde(deserialize_with = "color")]
+    pub search_match: Color,
+    #[serde(deserialize_with = "color")]
+    pub search_current: Color,
+    #[serde(deserialize_with = "color")]
+    pub status: Color,
+    #[serde(deserialize_with = "color")]
+    pub error: Color,
+}
+
+/// The `[keys]` table: user bindings per context.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeysConfig {
+    pub timeout: u64,
+    #[serde(default)]
+    pub global: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tree: BTreeMap<String, String>,
+    #[serde(default)]
+    pub preview: BTreeMap<String, String>,
+    #[serde(default)]
+    pub popup: BTreeMap<String, String>,
+}
+
+/// Styles of rendered markdown elements.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MarkdownStyles {
+    #[serde(deserialize_with = "style")]
+    pub text: Style,
+    #[serde(deserialize_with = "style")]
+    pub h1: Style,
+    #[serde(deserialize_with = "style")]
+    pub h2: Style,
+    #[serde(deserialize_with = "style")]
+    pub h3: Style,
+    #[serde(deserialize_with = "style")]
+    pub h4: Style,
+    #[serde(deserialize_with = "style")]
+    pub h5: Style,
+    #[serde(deserialize_with = "style")]
+    pub h6: Style,
+    #[serde(deserialize_with = "style")]
+    pub h1_underline: Style,
+    #[serde(deserialize_with = "style")]
+    pub strong: Style,
+    #[serde(deserialize_with = "style")]
+    pub emphasis: Style,
+    #[serde(deserialize_with = "style")]
+    pub strikethrough: Style,
+    #[serde(deserialize_with = "style")]
+    pub code: Style,
+    #[serde(deserialize_with = "style")]
+    pub code_block: Style,
+    #[serde(deserialize_with = "style")]
+    pub code_border: Style,
+    #[serde(deserialize_with = "style")]
+    pub code_language: Style,
+    #[serde(deserialize_with = "style")]
+    pub link: Style,
+    #[serde(deserialize_with = "style")]
+    pub image: Style,
+    #[serde(deserialize_with = "style")]
+    pub quote: Style,
+    #[serde(deserialize_with = "style")]
+    pub note: Style,
+    #[serde(deserialize_with = "style")]
+    pub tip: Style,
+    #[serde(deserialize_with = "style")]
+    pub important: Style,
+    #[serde(deserialize_with = "style")]
+    pub warning: Style,
+    #[serde(deserialize_with = "style")]
+    pub caution: Style,
+    #[serde(deserialize_with = "style")]
+    pub rule: Style,
+    #[serde(deserialize_with = "style")]
+    pub math: Style,
+    #[serde(deserialize_with = "style")]
+    pub footnote: Style,
+    #[serde(deserialize_with = "style")]
+    pub table_border: Style,
+    #[serde(deserialize_with = "style")]
+    pub table_header: Style,
+    #[serde(deserialize_with = "style")]
+    pub task_done: Style,
+    #[serde(deserialize_with = "style")]
+    pub task_open: Style,
+    #[serde(deserialize_with = "colors")]
+    pub bullets: Vec<Color>,
+}
+
+impl MarkdownStyles {
+    /// Style of a heading's text (and its `#` marker).
+    pub fn heading(&self, level: usize) -> Style {
+        match level {
+            1 => self.h1,
+            2 => self.h2,
+            3 => self.h3,
+            4 => self.h4,
+            5 => self.h5,
+            _ => self.h6,
+        }
+    }
+
+    /// Bullet color for a list nested `depth` levels deep (1-based).
+    pub fn bullet(&self, depth: usize) -> Color {
+        self.bullets[(depth.max(1) - 1) % self.bullets.len()]
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        parse(Table::new()).expect("built-in config is valid")
+    }
+}
+
+/// `$XDG_CONFIG_HOME/mdvim/config.toml`, falling back to `~/.config`.
+pub fn config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("mdvim").join("config.toml"))
+}
+
+/// Load the user config, if there is one. Errors name the file and the
+/// offending setting.
+pub fn load() -> Result<Config, String> {
+    let Some(path) = config_path().filter(|p| p.is_file()) else {
+        return Ok(Config::default());
+    };
+    let source = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let user: Table = toml::from_str(&source)
+        .map_err(|e| format!("{}: {}", path.display(), e.to_string().trim_end()))?;
+    parse(user).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Merge `user` over the defaults and validate the result.
+fn parse(user: Table) -> Result<Config, String> {
+    let mut merged: Table = toml::from_str(DEFAULT_CONFIG).expect("built-in config parses");
+    merge(&mut merged, user);
+    let config = Config {
+        theme: setting(&mut merged, "theme")?,
+        opener: setting(&mut merged, "opener")?,
+        tree: setting(&mut merged, "tree")?,
+        scroll: setting(&mut merged, "scroll")?,
+        colors: setting(&mut merged, "colors")?,
+        markdown: setting(&mut merged, "markdown")?,
+        keymap: Keymap::new(&setting(&mut merged, "keys")?)?,
+    };
+    if let Some(key) = merged.keys().next() {
+        return Err(format!("unknown setting '{}'", key));
+    }
+
+    let themes = crate::syntax::get_available_themes();
+    if !themes.contains(&config.theme) {
+        return Err(format!(
+            "unknown theme '{}' (available: {})",
+            config.theme,
+            themes.join(", ")
+        ));
+    }
+    if !(10..=80).contains(&config.tree.width) {
+        return Err(format!(
+            "invalid tree.width {} (expected 10 to 80)",
+            config.tree.width
+        ));
+    }
+    // `half-page` and `page` use 0 for "based on the window height".
+    for (key, lines) in [("line", config.scroll.line), ("mouse", config.scroll.mouse)] {
+        if lines == 0 {
+            return Err(format!("invalid scroll.{} 0 (expected at least 1)", key));
+        }
+    }
+    if config.opener.as_ref().is_some_and(|o| o.trim().is_empty()) {
+        return Err("opener must not be empty".to_string());
+    }
+    if config.markdown.bullets.is_empty() {
+        return Err("markdown.bullets must list at least one color".to_string());
+    }
+    Ok(config)
+}
+
+/// Take and deserialize one top-level setting, naming it in any error.
+fn setting<T: DeserializeOwned>(table: &mut Table, key: &str) -> Result<T, String> {
+    let value = table.remove(key);
+    let result = match value {
+        Some(value) => value.try_into(),
+        // Only optional settings are missing from the defaults.
+        None => T::deserialize(serde::de::value::UnitDeserializer::new()),
+    };
+    result.map_err(|e: toml::de::Error| format!("{}: {}", key, e.message().trim_end()))
+}
+
+/// Recursively merge `overlay` into `base`: tables merge, anything else replaces.
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    Color::from_str(s).map_err(|_| format!("invalid color '{}'", s))
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_color(&s).map_err(serde::de::Error::custom)
+}
+
+fn colors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| parse_color(s).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// A style as written in the config file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct StyleSpec {
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    bold: bool,
+    #[serde(default)]
+    italic: bool,
+    #[serde(default)]
+    underlined: bool,
+    #[serde(default)]
+    dim: bool,
+    #[serde(default)]
+    crossed_out: bool,
+}
+
+fn style<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Style, D::Error> {
+    let spec = StyleSpec::deserialize(deserializer)?;
+    let mut style = Style::default();
+    if let Some(fg) = &spec.fg {
+        style = style.fg(parse_color(fg).map_err(serde::de::Error::custom)?);
+    }
+    if let Some(bg) = &spec.bg {
+        style = style.bg(parse_color(bg).map_err(serde::de::Error::custom)?);
+    }
+    for (set, modifier) in [
+        (spec.bold, Modifier::BOLD),
+        (spec.italic, Modifier::ITALIC),
+        (spec.underlined, Modifier::UNDERLINED),
+        (spec.dim, Modifier::DIM),
+        (spec.crossed_out, Modifier::CROSSED_OUT),
+    ] {
+        if set {
+            style = style.add_modifier(modifier);
+        }
+    }
+    Ok(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> Result<Config, String> {
+        parse(toml::from_str(source).unwrap())
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert_eq!(
+            parse_str("colour = \"red\"").unwrap_err(),
+            "unknown setting 'colour'"
+        );
+        let err = parse_str("[tree]\nwidht = 30").unwrap_err();
+        assert!(err.starts_with("tree: unknown field `widht`"), "{}", err);
+    }
+
+    #[test]
+    fn bad_colors_name_the_setting() {
+        let err = parse_str("[colors]\naccent = \"pinkish\"").unwrap_err();
+        assert!(
+            err.starts_with("colors: invalid color 'pinkish'"),
+            "{}",
+            err
+        );
+        let err = parse_str("[markdown]\nh2 = { fg = \"#12345\" }").unwrap_err();
+        assert!(
+            err.starts_with("markdown: invalid color '#12345'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn tree_width_must_be_in_range() {
+        assert_eq!(
+            parse_str("[tree]\nwidth = 5").unwrap_err(),
+            "invalid tree.width 5 (expected 10 to 80)"
+        );
+        assert!(parse_str("[tree]\nwidth = 81").is_err());
+        assert_eq!(parse_str("[tree]\nwidth = 80").unwrap().tree.width, 80);
+    }
+
+    #[test]
+    fn scroll_steps_must_move() {
+        assert_eq!(
+            parse_str("[scroll]\nline = 0").unwrap_err(),
+            "invalid scroll.line 0 (expected at least 1)"
+        );
+        assert!(parse_str("[scroll]\nmouse = 0").is_err());
+    }
+
+    #[test]
+    fn bullets_must_not_be_empty() {
+        assert_eq!(
+            parse_str("[markdown]\nbullets = []").unwrap_err(),
+            "markdown.bullets must list at least one color"
+        );
+    }
+
+    #[test]
+    fn partial_tables_keep_the_defaults() {
+        let defaults = Config::default();
+        let config = parse_str("[markdown]\nh2 = { fg = \"red\" }\n[scroll]\npage = 40").unwrap();
+        // Styles merge too: the default h2 stays bold.
+        assert_eq!(config.markdown.h2, defaults.markdown.h2.fg(Color::Red));
+        assert_eq!(config.markdown.h1, defaults.markdown.h1);
+        assert_eq!(config.markdown.bullets, defaults.markdown.bullets);
+        assert_eq!(config.scroll.page, 40);
+        assert_eq!(config.scroll.half_page, defaults.scroll.half_page);
+        assert_eq!(config.theme, defaults.theme);
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+use ratatui::{layout::Rect, widgets::ListState};
+
+use crate::config::MarkdownStyles;
+use crate::renderer::{Rendered, markdown_to_ratatui};
+
+// Scoring for fuzzy matches, loosely after fzf: every matched character
+// scores, more so at the start of a word or right after the previous match,
+// and gaps between matches cost a little.
+const SCORE_MATCH: i64 = 16;
+const BONUS_PATH_START: i64 = 10;
+const BONUS_WORD_START: i64 = 8;
+const BONUS_CAMEL_CASE: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FILE_NAME: i64 = 2;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTEND: i64 = 1;
+
+/// A file matching the finder query.
+pub struct FinderMatch {
+    /// Index into `Finder::names`.
+    pub file: usize,
+    pub score: i64,
+    /// Character positions in the name that matched the query.
+    pub positions: Vec<usize>,
+}
+
+/// The Ctrl+p fuzzy finder over every markdown file in the project.
+pub struct Finder {
+    pub query: String,
+    files: Vec<PathBuf>,
+    /// Paths relative to the tree root, as shown in the list.
+    pub names: Vec<String>,
+    /// Matches for the query, best first.
+    pub matches: Vec<FinderMatch>,
+    pub state: ListState,
+    /// Rendered preview of the selected file, and the file and width it was
+    /// rendered for.
+    preview: Option<(usize, u16, Rendered)>,
+}
+
+impl Finder {
+    pub fn new(root: &Path) -> Self {
+        let files = project_files(root);
+        let names = files
+            .iter()
+            .map(|path| {
+                path.strip_prefix(root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+
+        let mut finder = Self {
+            query: String::new(),
+            files,
+            names,
+            matches: Vec::new(),
+            state: ListState::default(),
+            preview: None,
+        };
+        finder.update_matches();
+        finder
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update_matches();
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.update_matches();
+    }
+
+    /// Re-rank the files for the query and select the top hit.
+    fn update_matches(&mut self) {
+        // Spaces only separate words for the reader.
+        let pattern: Vec<char> = self.query.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut matches: Vec<FinderMatch> = self
+            .names
+            .iter()
+            .enumerate()
+            .filter_map(|(file, name)| {
+                let (score, positions) = fuzzy_match(&pattern, name)?;
+                Some(FinderMatch {
+                    file,
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+        // Shorter paths win ties; the list is already in path order.
+        matches.sort_by_key(|m| (-m.score, self.names[m.file].len()));
+        self.matches = matches;
+        self.state = ListState::default();
+        self.state.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            let i = self
+                .state
+                .selected()
+                .map_or(0, |i| (i + 1) % self.matches.len());
+            self.state.select(Some(i));
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if !self.matches.is_empty() {
+            let len = self.matches.len();
+            let i = self.state.selected().map_or(0, |i| (i + len - 1) % len);
+            self.state.select(Some(i));
+        }
+    }
+
+    fn selected_file(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|i| self.matches.get(i))
+            .map(|m| m.file)
+    }
+
+    pub fn selected_path(&self) -> Option<&Path> {
+        self.selected_file().map(|i| self.files[i].as_path())
+    }
+
+    /// The selected file rendered for `area` (the preview block including
+    /// its borders), cached until the selection or width changes.
+    pub fn preview(
+        &mut self,
+        theme: &str,
+        styles: &MarkdownStyles,
+        area: Rect,
+    ) -> Option<&Rendered> {
+        let file = self.selected_file()?;
+        let fresh = matches!(&self.preview, Some((f, w, _)) if *f == file && *w == area.width);
+        if !fresh {
+            let markdown = fs::read_to_string(&self.files[file])
+                .unwrap_or_else(|_| "Unable to read file".to_string());
+            let rendered = markdown_to_ratatui(&markdown, theme, styles, area, false);
+            self.preview = Some((file, area.width, rendered));
+        }
+        self.preview.as_ref().map(|(_, _, rendered)| rendered)
+    }
+}
+
+/// Every markdown file under `root` in path order, skipping hidden files and
+/// anything ignored by `.gitignore` (also outside git repositories).
+pub fn project_files(root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| crate::app::is_markdown_file(path))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Score `name` against the query characters, returning the score and the
+/// matched character positions, or `None` if they don't all appear in order.
+/// Matching is case-insensitive unless the query has an uppercase letter.
+fn fuzzy_match(pattern: &[char], name: &str) -> Option<(i64, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let chars: Vec<char> = name.chars().collect();
+    let case_sensitive = pattern.iter().any(|c| c.is_uppercase());
+    let eq = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    // Find where the first in-order match ends, then walk back from there
+    // for the shortest window that still contains the whole query.
+    let mut p = 0;
+    let mut end = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        if eq(c, pattern[p]) {
+            p += 1;
+            if p == pattern.len() {
+                end = i;
+                break;
+            }
+        }
+    }
+    if p < pattern.len() {
+        return None;
+    }
+    let mut start = end;
+    let mut p = pattern.len();
+    for i in (0..=end).rev() {
+        if eq(chars[i], pattern[p - 1]) {
+            p -= 1;
+            if p == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut p = 0;
+    for (i, &c) in chars.iter().enumerate().take(end + 1).skip(start) {
+        if p < pattern.len() && eq(c, pattern[p]) {
+            positions.push(i);
+            p += 1;
+        }
+    }
+
+    let file_name_start = chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &i in &positions {
+        score += SCORE_MATCH;
+        score += match i.checked_sub(1).map(|j| chars[j]) {
+            None | Some('/') => BONUS_PATH_START,
+            Some('_' | '-' | '.' | ' ') => BONUS_WORD_START,
+            Some(before) if before.is_lowercase() && chars[i].is_uppercase() => BONUS_CAMEL_CASE,
+            _ => 0,
+        };
+        if i >= file_name_start {
+            score += BONUS_FILE_NAME;
+        }
+        match previous {
+            Some(j) if j + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(j) => score -= PENALTY_GAP_START + PENALTY_GAP_EXTEND * (i - j - 2) as i64,
+            None => {}
+        }
+        previous = Some(i);
+    }
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, name: &str) -> Option<(i64, Vec<usize>)> {
+        fuzzy_match(&pattern.chars().collect::<Vec<_>>(), name)
+    }
+
+    #[test]
+    fn matches_in_order() {
+        assert_eq!(score("rdm", "README.md").unwrap().1, [0, 3, 4]);
+        assert_eq!(score("mdr", "README.md"), None);
+        assert_eq!(score("", "README.md"), Some((0, Vec::new())));
+    }
+
+    #[test]
+    fn uppercase_makes_the_query_case_sensitive() {
+        assert!(score("readme", "README.md").is_some());
+        assert!(score("Readme", "README.md").is_none());
+        assert!(score("README", "README.md").is_some());
+    }
+
+    #[test]
+    fn matches_use_the_shortest_window() {
+        // The first `n` is in `notes`, but the tightest match is `new`.
+        assert_eq!(score("new", "notes/new.md").unwrap().1, [6, 7, 8]);
+    }
+
+    #[test]
+    fn word_starts_and_file_names_score_higher() {
+        let (start, _) = score("ch", "docs/changes.md").unwrap();
+        let (middle, _) = score("ch", "docs/cache.md").unwrap();
+        assert!(start > middle);
+        let (file, _) = score("guide", "docs/guide.md").unwrap();
+        let (dir, _) = score("guide", "guide/index.md").unwrap();
+        assert!(file > dir);
+    }
+}
@@ -0,0 +1,35 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Branch checked out in the git repository containing `path`, or the short
+/// commit hash if HEAD is detached. `None` outside a repository.
+pub fn branch(path: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir(path)?.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+/// The `.git` directory of the repository containing `path`, following the
+/// `gitdir:` file that worktrees and submodules have instead.
+fn git_dir(path: &Path) -> Option<PathBuf> {
+    let path = path.canonicalize().ok()?;
+    for dir in path.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if let Ok(contents) = fs::read_to_string(&dot_git) {
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(target));
+        }
+    }
+    None
+}
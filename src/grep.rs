@@ -0,0 +1,207 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use ratatui::widgets::ListState;
+
+use crate::finder::project_files;
+use crate::search::build_regex;
+
+/// Most results listed; the search stops there.
+const MAX_RESULTS: usize = 1000;
+
+/// Characters of context kept before a match that is far into its line.
+const SNIPPET_CONTEXT: usize = 30;
+
+/// A source line matching the project search.
+pub struct GrepResult {
+    /// Index into `ProjectSearch::names`.
+    pub file: usize,
+    /// Line number in the file, starting at 1.
+    pub line: usize,
+    /// Byte offset of the match in the file.
+    pub offset: usize,
+    /// The line, trimmed and shortened so the match is near the start.
+    pub snippet: String,
+    /// Byte range of the match in `snippet`.
+    pub highlight: Range<usize>,
+}
+
+/// Full-text search over every markdown file under the tree root.
+pub struct ProjectSearch {
+    pub query: String,
+    files: Vec<PathBuf>,
+    /// Paths relative to the tree root, as shown in the results.
+    pub names: Vec<String>,
+    contents: Vec<String>,
+    /// Matching lines in path and line order.
+    pub results: Vec<GrepResult>,
+    /// Set when the results stop at `MAX_RESULTS`.
+    pub truncated: bool,
+    /// Why the query has no results, if it is not a valid pattern.
+    pub error: Option<String>,
+    pub state: ListState,
+}
+
+impl ProjectSearch {
+    /// Read the markdown files under `root`; they are searched as the query
+    /// is typed.
+    pub fn new(root: &Path) -> Self {
+        let (files, contents): (Vec<PathBuf>, Vec<String>) = project_files(root)
+            .into_iter()
+            .filter_map(|path| {
+                let content = fs::read_to_string(&path).ok()?;
+                Some((path, content))
+            })
+            .unzip();
+        let names = files
+            .iter()
+            .map(|path| {
+                path.strip_prefix(root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+
+        Self {
+            query: String::new(),
+            files,
+            names,
+            contents,
+            results: Vec::new(),
+            truncated: false,
+            error: None,
+            state: ListState::default(),
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update_results();
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.update_results();
+    }
+
+    /// Search the files for the query as a regex (smart-case, like `/`).
+    pub fn update_results(&mut self) {
+        self.results.clear();
+        self.truncated = false;
+        self.error = None;
+        self.state = ListState::default();
+        if self.query.is_empty() {
+            return;
+        }
+        let regex = match build_regex(&self.query) {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.error = Some(format!("Invalid pattern: {}", e));
+                return;
+            }
+        };
+
+        'files: for (file, content) in self.contents.iter().enumerate() {
+            let mut line_start = 0;
+            for (i, line) in content.split('\n').enumerate() {
+                if let Some(m) = regex.find(line).filter(|m| !m.is_empty()) {
+                    if self.results.len() == MAX_RESULTS {
+                        self.truncated = true;
+                        break 'files;
+                    }
+                    let (snippet, highlight) = snippet(line, m.range());
+                    self.results.push(GrepResult {
+                        file,
+                        line: i + 1,
+                        offset: line_start + m.start(),
+                        snippet,
+                        highlight,
+                    });
+                }
+                line_start += line.len() + 1;
+            }
+        }
+        self.state.select((!self.results.is_empty()).then_some(0));
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.results.is_empty() {
+            let i = self
+                .state
+                .selected()
+                .map_or(0, |i| (i + 1) % self.results.len());
+            self.state.select(Some(i));
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if !self.results.is_empty() {
+            let len = self.results.len();
+            let i = self.state.selected().map_or(0, |i| (i + len - 1) % len);
+            self.state.select(Some(i));
+        }
+    }
+
+    /// The file and byte offset of the selected result.
+    pub fn selected(&self) -> Option<(&Path, usize)> {
+        let result = self.results.get(self.state.selected()?)?;
+        Some((&self.files[result.file], result.offset))
+    }
+}
+
+/// Trim `line` for display, keeping some context before the match at
+/// `range`, and return the snippet with the match's range in it.
+fn snippet(line: &str, range: Range<usize>) -> (String, Range<usize>) {
+    let line = line.trim_end();
+    // The match may run into (or lie in) trailing whitespace that was trimmed.
+    let match_start = range.start.min(line.len());
+    let indent = line.len() - line.trim_start().len();
+    let mut start = indent.min(match_start);
+    let context = line[start..match_start].chars().count();
+    let mut prefix = "";
+    if context > SNIPPET_CONTEXT {
+        start = line[..match_start]
+            .char_indices()
+            .rev()
+            .nth(SNIPPET_CONTEXT - 1)
+            .map_or(start, |(i, _)| i);
+        prefix = "…";
+    }
+    let snippet = format!("{}{}", prefix, &line[start..]);
+    let shift = |offset: usize| (offset - start + prefix.len()).min(snippet.len());
+    let highlight = shift(range.start)..shift(range.end);
+    (snippet, highlight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippets_drop_indentation() {
+        assert_eq!(
+            snippet("    let x = 1;  ", 8..9),
+            ("let x = 1;".to_string(), 4..5)
+        );
+    }
+
+    #[test]
+    fn long_context_is_cut_with_an_ellipsis() {
+        let line = format!("{}match", "a".repeat(50));
+        let (text, range) = snippet(&line, 50..55);
+        assert_eq!(text, format!("…{}match", "a".repeat(SNIPPET_CONTEXT)));
+        assert_eq!(&text[range], "match");
+
+        let line = format!("{}x", "é".repeat(40));
+        let (text, range) = snippet(&line, 80..81);
+        assert_eq!(text, format!("…{}x", "é".repeat(SNIPPET_CONTEXT)));
+        assert_eq!(&text[range], "x");
+    }
+
+    #[test]
+    fn matches_in_trailing_whitespace_stay_in_bounds() {
+        assert_eq!(snippet("word   ", 5..6), ("word".to_string(), 4..4));
+    }
+}
@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::KeysConfig;
+
+/// Everything a key binding can do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ToggleTree,
+    ThemePicker,
+    FocusTree,
+    FocusPreview,
+    ToggleFocus,
+    ShrinkTree,
+    GrowTree,
+    CommandLine,
+    Finder,
+    ProjectSearch,
+    // Tree
+    SelectNext,
+    SelectPrev,
+    Expand,
+    Collapse,
+    Open,
+    // Preview
+    ScrollDown,
+    ScrollUp,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+    Top,
+    Bottom,
+    /// Go to the line given by the count (`50%`).
+    Percent,
+    NextBlock,
+    PrevBlock,
+    ScreenTop,
+    ScreenMiddle,
+    ScreenBottom,
+    CursorTop,
+    CursorCenter,
+    CursorBottom,
+    NextHeading,
+    PrevHeading,
+    Outline,
+    NextLink,
+    PrevLink,
+    FollowLink,
+    HistoryBack,
+    HistoryForward,
+    SearchForward,
+    SearchBackward,
+    SearchNext,
+    SearchPrev,
+    Copy,
+    CopySource,
+    Edit,
+    SourceView,
+    ToggleFolds,
+    Footnote,
+    NextBuffer,
+    PrevBuffer,
+    BufferList,
+    Cancel,
+    // Popups
+    Confirm,
+    Close,
+    /// Bound to a key to disable its default binding.
+    Nop,
+}
+
+/// Action names as written in the config file.
+const ACTION_NAMES: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+    ("toggle-tree", Action::ToggleTree),
+    ("theme-picker", Action::ThemePicker),
+    ("focus-tree", Action::FocusTree),
+    ("focus-preview", Action::FocusPreview),
+    ("toggle-focus", Action::ToggleFocus),
+    ("shrink-tree", Action::ShrinkTree),
+    ("grow-tree", Action::GrowTree),
+    ("command-line", Action::CommandLine),
+    ("finder", Action::Finder),
+    ("project-search", Action::ProjectSearch),
+    ("select-next", Action::SelectNext),
+    ("select-prev", Action::SelectPrev),
+    ("expand", Action::Expand),
+    ("collapse", Action::Collapse),
+    ("open", Action::Open),
+    ("scroll-down", Action::ScrollDown),
+    ("scroll-up", Action::ScrollUp),
+    ("half-page-down", Action::HalfPageDown),
+    ("half-page-up", Action::HalfPageUp),
+    ("page-down", Action::PageDown),
+    ("page-up", Action::PageUp),
+    ("top", Action::Top),
+    ("bottom", Action::Bottom),
+    ("percent", Action::Percent),
+    ("next-block", Action::NextBlock),
+    ("prev-block", Action::PrevBlock),
+    ("screen-top", Action::ScreenTop),
+    ("screen-middle", Action::ScreenMiddle),
+    ("screen-bottom", Action::ScreenBottom),
+    ("cursor-top", Action::CursorTop),
+    ("cursor-center", Action::CursorCenter),
+    ("cursor-bottom", Action::CursorBottom),
+    ("next-heading", Action::NextHeading),
+    ("prev-heading", Action::PrevHeading),
+    ("outline", Action::Outline),
+    ("next-link", Action::NextLink),
+    ("prev-link", Action::PrevLink),
+    ("follow-link", Action::FollowLink),
+    ("history-back", Action::HistoryBack),
+    ("history-forward", Action::HistoryForward),
+    ("search-forward", Action::SearchForward),
+    ("search-backward", Action::SearchBackward),
+    ("search-next", Action::SearchNext),
+    ("search-prev", Action::SearchPrev),
+    ("copy", Action::Copy),
+    ("copy-source", Action::CopySource),
+    ("edit", Action::Edit),
+    ("source-view", Action::SourceView),
+    ("toggle-folds", Action::ToggleFolds),
+    ("footnote", Action::Footnote),
+    ("next-buffer", Action::NextBuffer),
+    ("prev-buffer", Action::PrevBuffer),
+    ("buffer-list", Action::BufferList),
+    ("cancel", Action::Cancel),
+    ("confirm", Action::Confirm),
+    ("close", Action::Close),
+    ("nop", Action::Nop),
+];
+
+impl Action {
+    fn from_name(name: &str) -> Option<Self> {
+        ACTION_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|&(_, action)| action)
+    }
+}
+
+/// Where a binding applies. Tree and preview bindings take precedence over
+/// global ones while that pane is focused; popups only see their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Context {
+    Global,
+    Tree,
+    Preview,
+    Popup,
+}
+
+const DEFAULT_BINDINGS: &[(Context, &str, Action)] = &[
+    (Context::Global, "q", Action::Quit),
+    (Context::Global, "<C-n>", Action::ToggleTree),
+    (Context::Global, "t", Action::ToggleTree),
+    (Context::Global, "ct", Action::ThemePicker),
+    (Context::Global, "<C-h>", Action::FocusTree),
+    (Context::Global, "<C-l>", Action::FocusPreview),
+    (Context::Global, "<C-w><C-w>", Action::ToggleFocus),
+    (Context::Global, "<C-Left>", Action::ShrinkTree),
+    (Context::Global, "<C-Right>", Action::GrowTree),
+    (Context::Global, ":", Action::CommandLine),
+    (Context::Global, "<C-p>", Action::Finder),
+    (Context::Global, "<C-g>", Action::ProjectSearch),
+    (Context::Global, "j", Action::ScrollDown),
+    (Context::Global, "<Down>", Action::ScrollDown),
+    (Context::Global, "k", Action::ScrollUp),
+    (Context::Global, "<Up>", Action::ScrollUp),
+    (Context::Global, "<C-d>", Action::HalfPageDown),
+    (Context::Global, "<C-u>", Action::HalfPageUp),
+    (Context::Global, "<PageDown>", Action::PageDown),
+    (Context::Global, "<PageUp>", Action::PageUp),
+    (Context::Global, "gg", Action::Top),
+    (Context::Global, "<Home>", Action::Top),
+    (Context::Global, "G", Action::Bottom),
+    (Context::Global, "<End>", Action::Bottom),
+    (Context::Global, "]]", Action::NextHeading),
+    (Context::Global, "[[", Action::PrevHeading),
+    (Context::Global, "O", Action::Outline),
+    (Context::Global, "<Tab>", Action::NextLink),
+    (Context::Global, "<S-Tab>", Action::PrevLink),
+    (Context::Global, "<C-o>", Action::HistoryBack),
+    (Context::Global, "<C-i>", Action::HistoryForward),
+    (Context::Global, "/", Action::SearchForward),
+    (Context::Global, "?", Action::SearchBackward),
+    (Context::Global, "n", Action::SearchNext),
+    (Context::Global, "N", Action::SearchPrev),
+    (Context::Global, "y", Action::Copy),
+    (Context::Global, "Y", Action::CopySource),
+    (Context::Global, "e", Action::Edit),
+    (Context::Global, "s", Action::SourceView),
+    (Context::Global, "gt", Action::NextBuffer),
+    (Context::Global, "gT", Action::PrevBuffer),
+    (Context::Global, "gb", Action::BufferList),
+    (Context::Global, "<Esc>", Action::Cancel),
+    (Context::Global, "o", Action::Open),
+    (Context::Tree, "j", Action::SelectNext),
+    (Context::Tree, "<Down>", Action::SelectNext),
+    (Context::Tree, "k", Action::SelectPrev),
+    (Context::Tree, "<Up>", Action::SelectPrev),
+    (Context::Tree, "l", Action::Expand),
+    (Context::Tree, "<Right>", Action::Expand),
+    (Context::Tree, "h", Action::Collapse),
+    (Context::Tree, "<Left>", Action::Collapse),
+    (Context::Tree, "<CR>", Action::Open),
+    (Context::Preview, "<CR>", Action::FollowLink),
+    (Context::Preview, "%", Action::Percent),
+    (Context::Preview, "}", Action::NextBlock),
+    (Context::Preview, "{", Action::PrevBlock),
+    (Context::Preview, "H", Action::ScreenTop),
+    (Context::Preview, "M", Action::ScreenMiddle),
+    (Context::Preview, "L", Action::ScreenBottom),
+    (Context::Preview, "zt", Action::CursorTop),
+    (Context::Preview, "zz", Action::CursorCenter),
+    (Context::Preview, "zb", Action::CursorBottom),
+    (Context::Preview, "zi", Action::ToggleFolds),
+    (Context::Preview, "gd", Action::Footnote),
+    (Context::Popup, "j", Action::SelectNext),
+    (Context::Popup, "<Down>", Action::SelectNext),
+    (Context::Popup, "k", Action::SelectPrev),
+    (Context::Popup, "<Up>", Action::SelectPrev),
+    (Context::Popup, "<CR>", Action::Confirm),
+    (Context::Popup, "<Esc>", Action::Close),
+    (Context::Popup, "O", Action::Close),
+];
+
+/// A single key press, normalized so that `G` and `Shift+g` compare equal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyPress {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyPress {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut mask = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        // Shift is already part of the character (and of BackTab).
+        if !matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            mask |= KeyModifiers::SHIFT;
+        }
+        Self {
+            code,
+            modifiers: modifiers & mask,
+        }
+    }
+
+    /// The digit typed, for count prefixes.
+    pub fn digit(&self) -> Option<usize> {
+        match self.code {
+            KeyCode::Char(c) if self.modifiers.is_empty() => c.to_digit(10).map(|d| d as usize),
+            _ => None,
+        }
+    }
+}
+
+impl From<KeyEvent> for KeyPress {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char('<') => "lt".to_string(),
+            KeyCode::Char(c) if self.modifiers.is_empty() => return write!(f, "{}", c),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => "CR".to_string(),
+            KeyCode::BackTab => "S-Tab".to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => format!("{:?}", code),
+        };
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("C-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("A-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            prefix.push_str("S-");
+        }
+        write!(f, "<{}{}>", prefix, name)
+    }
+}
+
+/// Parse a vim-style key sequence such as `gg`, `<C-w><C-w>` or `<S-Tab>`.
+fn parse_sequence(keys: &str) -> Result<Vec<KeyPress>, String> {
+    let mut sequence = Vec::new();
+    let mut rest = keys;
+    while let Some(c) = rest.chars().next() {
+        if c == '<'
+            && let Some(end) = rest.find('>')
+            && end > 1
+        {
+            sequence.push(
+                parse_special(&rest[1..end])
+                    .ok_or_else(|| format!("unknown key '{}' in '{}'", &rest[..=end], keys))?,
+            );
+            rest = &rest[end + 1..];
+        } else {
+            sequence.push(KeyPress::new(KeyCode::Char(c), KeyModifiers::NONE));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if sequence.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(sequence)
+}
+
+/// Parse the inside of a `<...>` key, e.g. `C-w`, `A-Left` or `PageDown`.
+fn parse_special(name: &str) -> Option<KeyPress> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    let mut shift = false;
+    while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+        match rest.as_bytes()[0].to_ascii_uppercase() {
+            b'C' => modifiers |= KeyModifiers::CONTROL,
+            b'A' | b'M' => modifiers |= KeyModifiers::ALT,
+            b'S' => shift = true,
+            _ => return None,
+        }
+        rest = &rest[2..];
+    }
+
+    let code = match rest.to_ascii_lowercase().as_str() {
+        "cr" | "enter" | "return" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" if shift => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "del" | "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        lower => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if shift => KeyCode::Char(c.to_ascii_uppercase()),
+                (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+                    // Terminals report Ctrl+letter in lowercase.
+                    KeyCode::Char(c.to_ascii_lowercase())
+                }
+                (Some(c), None) => KeyCode::Char(c),
+                _ => {
+                    let n = lower.strip_prefix('f')?.parse::<u8>().ok()?;
+                    KeyCode::F(n)
+                }
+            }
+        }
+    };
+    if shift {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    Some(KeyPress::new(code, modifiers))
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    action: Option<Action>,
+    children: HashMap<KeyPress, Node>,
+}
+
+/// Result of looking up the keys typed so far.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// A complete binding with no longer binding sharing its prefix.
+    Action(Action),
+    /// A prefix of a longer binding; `action` is what the keys typed so far
+    /// are bound to on their own, run if the sequence times out.
+    Pending(Option<Action>),
+    NotFound,
+}
+
+/// Key bindings per context, as a trie of key presses.
+#[derive(Debug)]
+pub struct Keymap {
+    contexts: HashMap<Context, Node>,
+    /// How long to wait for the next key of a sequence, in milliseconds.
+    pub timeout: u64,
+}
+
+impl Keymap {
+    /// The default bindings with the user's overrides from `[keys]` applied.
+    pub fn new(config: &KeysConfig) -> Result<Self, String> {
+        let mut keymap = Self {
+            contexts: HashMap::new(),
+            timeout: config.timeout,
+        };
+        for &(context, keys, action) in DEFAULT_BINDINGS {
+            let sequence = parse_sequence(keys).expect("default binding parses");
+            keymap.bind(context, &sequence, action);
+        }
+
+        for (name, context, bindings) in [
+            ("global", Context::Global, &config.global),
+            ("tree", Context::Tree, &config.tree),
+            ("preview", Context::Preview, &config.preview),
+            ("popup", Context::Popup, &config.popup),
+        ] {
+            for (keys, action_name) in bindings {
+                let sequence = parse_sequence(keys).map_err(|e| format!("keys.{}: {}", name, e))?;
+                let action = Action::from_name(action_name)
+                    .ok_or_else(|| format!("keys.{}: unknown action '{}'", name, action_name))?;
+                keymap.bind(context, &sequence, action);
+            }
+        }
+        Ok(keymap)
+    }
+
+    fn bind(&mut self, context: Context, sequence: &[KeyPress], action: Action) {
+        let mut node = self.contexts.entry(context).or_default();
+        for key in sequence {
+            node = node.children.entry(*key).or_default();
+        }
+        node.action = Some(action);
+    }
+
+    /// Look up `keys` in the first of `contexts` that knows them.
+    pub fn lookup(&self, contexts: &[Context], keys: &[KeyPress]) -> Lookup {
+        for context in contexts {
+            let Some(mut node) = self.contexts.get(context) else {
+                continue;
+            };
+            let mut found = true;
+            for key in keys {
+                match node.children.get(key) {
+                    Some(child) => node = child,
+                    None => {
+                        found = false;
+                        break;
+                    }
+                }
+            }
+            if !found {
+                continue;
+            }
+            if !node.children.is_empty() {
+                return Lookup::Pending(node.action);
+            }
+            if let Some(action) = node.action {
+                return Lookup::Action(action);
+            }
+        }
+        Lookup::NotFound
+    }
+}
+
+/// Keys typed so far in a multi-key sequence, for display.
+pub fn format_keys(keys: &[KeyPress]) -> String {
+    keys.iter().map(KeyPress::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn keys(sequence: &str) -> Vec<KeyPress> {
+        parse_sequence(sequence).unwrap()
+    }
+
+    fn keymap(preview: &[(&str, &str)]) -> Result<Keymap, String> {
+        Keymap::new(&KeysConfig {
+            timeout: 1000,
+            global: BTreeMap::new(),
+            tree: BTreeMap::new(),
+            preview: preview
+                .iter()
+                .map(|&(keys, action)| (keys.to_string(), action.to_string()))
+                .collect(),
+            popup: BTreeMap::new(),
+        })
+    }
+
+    const PREVIEW: &[Context] = &[Context::Preview, Context::Global];
+
+    #[test]
+    fn sequences_fall_back_to_global() {
+        let keymap = keymap(&[("gx", "bottom")]).unwrap();
+        assert_eq!(keymap.lookup(PREVIEW, &keys("g")), Lookup::Pending(None));
+        assert_eq!(
+            keymap.lookup(PREVIEW, &keys("gx")),
+            Lookup::Action(Action::Bottom)
+        );
+        // Preview knows `g` but not `gg`, so the global binding wins.
+        assert_eq!(
+            keymap.lookup(PREVIEW, &keys("gg")),
+            Lookup::Action(Action::Top)
+        );
+        assert_eq!(keymap.lookup(PREVIEW, &keys("gz")), Lookup::NotFound);
+    }
+
+    #[test]
+    fn nop_shadows_a_global_binding() {
+        let keymap = keymap(&[("q", "nop")]).unwrap();
+        assert_eq!(
+            keymap.lookup(PREVIEW, &keys("q")),
+            Lookup::Action(Action::Nop)
+        );
+        assert_eq!(
+            keymap.lookup(&[Context::Tree, Context::Global], &keys("q")),
+            Lookup::Action(Action::Quit)
+        );
+    }
+
+    #[test]
+    fn special_key_notation() {
+        assert_eq!(
+            keys("<C-x>"),
+            [KeyPress::new(KeyCode::Char('x'), KeyModifiers::CONTROL)]
+        );
+        assert_eq!(keys("<C-X>"), keys("<c-x>"));
+        assert_eq!(
+            keys("<lt>a"),
+            [
+                KeyPress::new(KeyCode::Char('<'), KeyModifiers::NONE),
+                KeyPress::new(KeyCode::Char('a'), KeyModifiers::NONE),
+            ]
+        );
+        assert_eq!(
+            keys("<S-Tab>"),
+            [KeyPress::new(KeyCode::BackTab, KeyModifiers::SHIFT)]
+        );
+        assert_eq!(keys("<S-g>"), keys("G"));
+        assert_eq!(format_keys(&keys("<lt><C-w>g")), "<lt><C-w>g");
+    }
+
+    #[test]
+    fn invalid_sequences_are_rejected() {
+        assert_eq!(parse_sequence(""), Err("empty key sequence".to_string()));
+        assert_eq!(
+            parse_sequence("g<C-foo>"),
+            Err("unknown key '<C-foo>' in 'g<C-foo>'".to_string())
+        );
+        assert!(parse_sequence("<X-a>").is_err());
+        assert_eq!(
+            keymap(&[("<Nope>", "top")]).unwrap_err(),
+            "keys.preview: unknown key '<Nope>' in '<Nope>'"
+        );
+        assert_eq!(
+            keymap(&[("x", "fly")]).unwrap_err(),
+            "keys.preview: unknown action 'fly'"
+        );
+    }
+}
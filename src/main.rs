@@ -1,173 +1,199 @@
 mod app;
+mod cli;
+mod command;
+mod config;
+mod finder;
+mod git;
+mod grep;
+mod keymap;
 mod renderer;
+mod search;
 mod syntax;
+mod tree;
 mod ui;
+mod watcher;
 
 use std::io;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crossterm::{
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
-        MouseEventKind,
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers,
+        KeyboardEnhancementFlags, MouseButton, MouseEventKind, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
     },
     execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    terminal::{
+        EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+        supports_keyboard_enhancement,
+    },
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
 
-use app::App;
+use app::{App, ScreenPosition};
+use keymap::Action;
 use ui::render_ui;
 
+/// How often to check for file changes while waiting for input.
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
+
 fn main() -> Result<(), io::Error> {
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(cli::Command::Run(args)) => args,
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(cli::Command::Version) => {
+            println!("mdvim {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(msg) => {
+            eprintln!("mdvim: {}\nTry 'mdvim --help' for more information.", msg);
+            process::exit(2);
+        }
+    };
+
+    // Set up the app before touching the terminal so errors print normally.
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(msg) => {
+            eprintln!("mdvim: {}", msg);
+            process::exit(1);
+        }
+    };
+    let mut app = match App::new(&args, config) {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("mdvim: {}", e);
+            process::exit(1);
+        }
+    };
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    // Lets terminals that support it report Ctrl+i separately from Tab.
+    let keyboard_enhanced = matches!(supports_keyboard_enhancement(), Ok(true));
+    if keyboard_enhanced {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
+    // Where a source line ends up depends on the preview width, known once drawn.
+    if let Some(line) = args.line {
+        terminal.draw(|f| render_ui(f, &mut app))?;
+        app.goto_source_line(line);
+    }
 
-    loop {
+    'main: loop {
+        if app.edit_requested {
+            app.edit_requested = false;
+            suspend_terminal(&mut terminal, keyboard_enhanced)?;
+            app.edit_in_editor();
+            resume_terminal(&mut terminal, keyboard_enhanced)?;
+        }
         terminal.draw(|f| render_ui(f, &mut app))?;
 
-        let evt = event::read()?;
+        // Wait for input, redrawing early if a watched file changes on disk
+        // or a pending key sequence times out.
+        let evt = loop {
+            let timeout = app
+                .pending_key_timeout()
+                .map_or(WATCH_INTERVAL, |t| t.min(WATCH_INTERVAL));
+            if event::poll(timeout)? {
+                break Some(event::read()?);
+            }
+            if let Some(action) = app.expire_pending_keys() {
+                if !run_action(&mut app, action) {
+                    break 'main;
+                }
+                break None;
+            }
+            if app.process_file_changes() {
+                break None;
+            }
+        };
+        let Some(evt) = evt else {
+            continue;
+        };
 
         match evt {
             Event::Key(key) => {
                 let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                // Messages last until the next key press, like in vim.
+                app.status_message = None;
 
-                if app.show_theme_list {
+                // The prompts take text input rather than bindings.
+                if app.finder.is_some() {
                     match key.code {
-                        KeyCode::Esc => app.toggle_theme_list(),
-                        KeyCode::Enter => app.confirm_theme_selection(),
-                        KeyCode::Char('j') | KeyCode::Down => app.next_theme_selection(),
-                        KeyCode::Char('k') | KeyCode::Up => app.prev_theme_selection(),
+                        KeyCode::Esc => app.close_finder(),
+                        KeyCode::Enter => app.confirm_finder(),
+                        KeyCode::Backspace => app.finder_backspace(),
+                        KeyCode::Down | KeyCode::Tab => app.finder_select(true),
+                        KeyCode::Up | KeyCode::BackTab => app.finder_select(false),
+                        KeyCode::Char('n' | 'j') if ctrl => app.finder_select(true),
+                        KeyCode::Char('p' | 'k') if ctrl => app.finder_select(false),
+                        KeyCode::Char(c) if !ctrl => app.finder_input(c),
                         _ => {}
                     }
                     continue;
                 }
-
-                match key.code {
-                    KeyCode::Char('q') => break,
-
-                    // Toggle file tree visibility
-                    KeyCode::Char('n') if ctrl => {
-                        app.toggle_tree();
-                    }
-
-                    KeyCode::Char('y') => {
-                        app.copy_selection();
-                    }
-
-                    KeyCode::Char('t') => {
-                        if app.last_key == Some('c') {
-                            app.toggle_theme_list();
-                            app.last_key = None;
-                        } else {
-                            app.toggle_tree();
-                        }
-                    }
-
-                    KeyCode::Char('c') => {
-                        app.last_key = Some('c');
-                    }
-
-                    // Focus movement: Ctrl+h (tree), Ctrl+l (preview)
-                    KeyCode::Char('h') if ctrl => {
-                        app.focus_tree();
-                    }
-
-                    KeyCode::Char('l') if ctrl => {
-                        app.focus_preview();
-                    }
-
-                    // Ctrl+ww (like vim: toggle focus between panes)
-                    KeyCode::Char('w') if ctrl => {
-                        if app.last_key == Some('w') {
-                            app.toggle_focus();
-                            app.last_key = None;
-                        } else {
-                            app.last_key = Some('w');
-                        }
-                    }
-
-                    // Vim-style gg / G navigation in preview
-                    KeyCode::Char('g') => {
-                        if app.last_key == Some('g') {
-                            app.scroll_to_top();
-                        } else {
-                            app.last_key = Some('g');
-                        }
-                    }
-
-                    KeyCode::Char('G') => {
-                        app.scroll_to_bottom();
-                    }
-
-                    // j / k depending on which pane is focused
-                    KeyCode::Char('j') | KeyCode::Down => {
-                        if app.show_tree && matches!(app.focused_pane, app::FocusedPane::FileTree) {
-                            app.next_file();
-                        } else {
-                            app.scroll_down(1);
-                        }
+                if app.project_search.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.close_project_search(),
+                        KeyCode::Enter => app.confirm_project_search(),
+                        KeyCode::Backspace => app.project_search_backspace(),
+                        KeyCode::Down | KeyCode::Tab => app.project_search_select(true),
+                        KeyCode::Up | KeyCode::BackTab => app.project_search_select(false),
+                        KeyCode::Char('n' | 'j') if ctrl => app.project_search_select(true),
+                        KeyCode::Char('p' | 'k') if ctrl => app.project_search_select(false),
+                        KeyCode::Char(c) if !ctrl => app.project_search_input(c),
+                        _ => {}
                     }
-
-                    KeyCode::Char('k') | KeyCode::Up => {
-                        if app.show_tree && matches!(app.focused_pane, app::FocusedPane::FileTree) {
-                            app.prev_file();
-                        } else {
-                            app.scroll_up(1);
+                    continue;
+                }
+                if app.command_prompt.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.cancel_command(),
+                        KeyCode::Enter => {
+                            let keep_running = app.confirm_command();
+                            if !keep_running {
+                                break 'main;
+                            }
                         }
+                        KeyCode::Backspace => app.command_backspace(),
+                        KeyCode::Tab => app.complete_command(false),
+                        KeyCode::BackTab => app.complete_command(true),
+                        KeyCode::Up => app.browse_command_history(true),
+                        KeyCode::Down => app.browse_command_history(false),
+                        KeyCode::Char(c) if !ctrl => app.command_input(c),
+                        _ => {}
                     }
-
-                    // Faster scrolling
-                    KeyCode::Char('d') if ctrl => {
-                        app.scroll_down(10);
-                    }
-
-                    KeyCode::Char('u') if ctrl => {
-                        app.scroll_up(10);
-                    }
-
-                    KeyCode::PageDown => {
-                        app.scroll_down(20);
-                    }
-
-                    KeyCode::PageUp => {
-                        app.scroll_up(20);
-                    }
-
-                    KeyCode::Home => {
-                        app.scroll_to_top();
-                    }
-
-                    KeyCode::End => {
-                        app.scroll_to_bottom();
-                    }
-
-                    // Open file from tree (only when visible)
-                    KeyCode::Enter if app.show_tree => {
-                        app.open_selected_file();
-                    }
-
-                    KeyCode::Char('o') if app.show_tree => {
-                        app.open_selected_file();
-                    }
-
-                    // Resize tree: Ctrl+Left shrink, Ctrl+Right grow
-                    KeyCode::Left if ctrl => {
-                        app.decrease_tree_width();
-                    }
-
-                    KeyCode::Right if ctrl => {
-                        app.increase_tree_width();
+                    continue;
+                }
+                if app.search_prompt.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.cancel_search(),
+                        KeyCode::Enter => app.confirm_search(),
+                        KeyCode::Backspace => app.search_backspace(),
+                        KeyCode::Char(c) if !ctrl => app.search_input(c),
+                        _ => {}
                     }
+                    continue;
+                }
 
-                    _ => {
-                        app.last_key = None;
-                    }
+                let actions = app.press_key(key.into());
+                if !actions
+                    .into_iter()
+                    .all(|action| run_action(&mut app, action))
+                {
+                    break;
                 }
             }
             Event::Mouse(mouse) => {
@@ -177,17 +203,15 @@ fn main() -> Result<(), io::Error> {
                     .as_millis() as u64;
 
                 match mouse.kind {
-                    MouseEventKind::ScrollDown => {
-                        if app.focused_pane == app::FocusedPane::Preview {
-                            app.scroll_down(3);
-                        }
+                    MouseEventKind::ScrollDown if app.focused_pane == app::FocusedPane::Preview => {
+                        app.scroll_down(app.config.scroll.mouse);
                     }
-                    MouseEventKind::ScrollUp => {
-                        if app.focused_pane == app::FocusedPane::Preview {
-                            app.scroll_up(3);
-                        }
+                    MouseEventKind::ScrollUp if app.focused_pane == app::FocusedPane::Preview => {
+                        app.scroll_up(app.config.scroll.mouse);
                     }
                     MouseEventKind::Down(MouseButton::Left) => {
+                        app.clear_pending_keys();
+                        app.count = None;
                         if app.show_tree {
                             let divider_x = app.last_tree_width_px;
                             let col = mouse.column;
@@ -195,11 +219,7 @@ fn main() -> Result<(), io::Error> {
                             // Check if clicking near divider for resizing
                             let mut is_dragging = false;
                             if divider_x > 0 {
-                                let diff = if col > divider_x {
-                                    col - divider_x
-                                } else {
-                                    divider_x - col
-                                };
+                                let diff = col.abs_diff(divider_x);
                                 if diff <= 1 {
                                     app.begin_divider_drag();
                                     is_dragging = true;
@@ -211,8 +231,8 @@ fn main() -> Result<(), io::Error> {
                                 // Determine which pane was clicked
                                 if col < divider_x {
                                     // Clicked on tree view
-                                    // Tree content starts at row 2 (after border + title), so subtract 2
-                                    let tree_start_row = 2;
+                                    // Tree content starts below the titled border (and the tab bar)
+                                    let tree_start_row = app.last_preview_area.y + 1;
                                     app.handle_tree_click(
                                         mouse.row,
                                         tree_start_row,
@@ -228,12 +248,13 @@ fn main() -> Result<(), io::Error> {
                                     } else {
                                         0
                                     };
-                                    if mouse.column >= preview_x {
+                                    if mouse.column >= preview_x && app.in_preview(mouse.column) {
                                         let rel_col = mouse
                                             .column
                                             .saturating_sub(preview_x)
                                             .saturating_sub(1);
-                                        let rel_row = mouse.row.saturating_sub(1);
+                                        let rel_row =
+                                            mouse.row.saturating_sub(app.last_preview_area.y + 1);
                                         app.start_selection(rel_col, rel_row);
                                     }
                                 }
@@ -241,9 +262,11 @@ fn main() -> Result<(), io::Error> {
                         } else {
                             // Only preview visible, focus it
                             app.handle_preview_click();
-                            let rel_col = mouse.column.saturating_sub(1);
-                            let rel_row = mouse.row.saturating_sub(1);
-                            app.start_selection(rel_col, rel_row);
+                            if app.in_preview(mouse.column) {
+                                let rel_col = mouse.column.saturating_sub(1);
+                                let rel_row = mouse.row.saturating_sub(app.last_preview_area.y + 1);
+                                app.start_selection(rel_col, rel_row);
+                            }
                         }
                     }
                     MouseEventKind::Drag(MouseButton::Left) => {
@@ -259,16 +282,14 @@ fn main() -> Result<(), io::Error> {
                             if mouse.column >= preview_x {
                                 let rel_col =
                                     mouse.column.saturating_sub(preview_x).saturating_sub(1);
-                                let rel_row = mouse.row.saturating_sub(1);
+                                let rel_row = mouse.row.saturating_sub(app.last_preview_area.y + 1);
                                 app.update_selection(rel_col, rel_row);
                             }
                         }
                     }
-                    MouseEventKind::Up(MouseButton::Left) => {
-                        if app.dragging_divider {
-                            app.set_tree_width_from_column(mouse.column);
-                            app.end_divider_drag();
-                        }
+                    MouseEventKind::Up(MouseButton::Left) if app.dragging_divider => {
+                        app.set_tree_width_from_column(mouse.column);
+                        app.end_divider_drag();
                     }
                     _ => {}
                 }
@@ -277,13 +298,142 @@ fn main() -> Result<(), io::Error> {
         }
     }
 
+    suspend_terminal(&mut terminal, keyboard_enhanced)?;
+    Ok(())
+}
+
+/// Give the terminal back to the shell (on exit, or while the editor runs).
+fn suspend_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    keyboard_enhanced: bool,
+) -> io::Result<()> {
+    if keyboard_enhanced {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
         DisableMouseCapture
     )?;
-    terminal.show_cursor()?;
+    terminal.show_cursor()
+}
 
-    Ok(())
+/// Take the terminal back after the editor exits and redraw from scratch.
+fn resume_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    keyboard_enhanced: bool,
+) -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    if keyboard_enhanced {
+        execute!(
+            terminal.backend_mut(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+    }
+    terminal.clear()
+}
+
+/// Run the action bound to a key, with the count typed before it.
+/// Returns `false` when mdvim should quit.
+fn run_action(app: &mut App, action: Action) -> bool {
+    let count = app.count.take();
+    let n = count.unwrap_or(1);
+    match action {
+        Action::Quit => return false,
+        Action::ToggleTree => app.toggle_tree(),
+        Action::ThemePicker => app.toggle_theme_list(),
+        Action::FocusTree => app.focus_tree(),
+        Action::FocusPreview => app.focus_preview(),
+        Action::ToggleFocus => app.toggle_focus(),
+        Action::ShrinkTree => app.decrease_tree_width(),
+        Action::GrowTree => app.increase_tree_width(),
+        Action::CommandLine => app.start_command(),
+        Action::Finder => app.open_finder(),
+        Action::ProjectSearch => app.open_project_search(None),
+
+        // Popups and the tree share the list motions.
+        Action::SelectNext if app.show_theme_list => app.next_theme_selection(),
+        Action::SelectNext if app.show_outline => app.next_outline_selection(),
+        Action::SelectNext if app.show_buffer_list => app.next_buffer_selection(),
+        Action::SelectNext => (0..n).for_each(|_| app.next_file()),
+        Action::SelectPrev if app.show_theme_list => app.prev_theme_selection(),
+        Action::SelectPrev if app.show_outline => app.prev_outline_selection(),
+        Action::SelectPrev if app.show_buffer_list => app.prev_buffer_selection(),
+        Action::SelectPrev => (0..n).for_each(|_| app.prev_file()),
+        Action::Confirm if app.show_theme_list => app.confirm_theme_selection(),
+        Action::Confirm if app.show_outline => app.confirm_outline_selection(),
+        Action::Confirm if app.show_buffer_list => app.confirm_buffer_selection(),
+        Action::Close if app.show_theme_list => app.toggle_theme_list(),
+        Action::Close if app.show_outline => app.toggle_outline(),
+        Action::Close if app.show_buffer_list => app.toggle_buffer_list(),
+        Action::Confirm | Action::Close => {}
+
+        Action::Expand => app.expand_selected(),
+        Action::Collapse => app.collapse_selected(),
+        Action::Open if app.show_tree => app.open_selected_file(),
+        Action::Open => {}
+
+        Action::ScrollDown => app.move_cursor((n * app.config.scroll.line as usize) as isize),
+        Action::ScrollUp => app.move_cursor(-((n * app.config.scroll.line as usize) as isize)),
+        Action::HalfPageDown => app.move_cursor(app.half_page(count) as isize),
+        Action::HalfPageUp => app.move_cursor(-(app.half_page(count) as isize)),
+        Action::PageDown => app.move_cursor((n * app.page()) as isize),
+        Action::PageUp => app.move_cursor(-((n * app.page()) as isize)),
+        // With a count, gg and G go to that line.
+        Action::Top | Action::Bottom if count.is_some() => app.goto_line(n),
+        Action::Top => app.scroll_to_top(),
+        Action::Bottom => app.scroll_to_bottom(),
+        Action::Percent if count.is_some() => app.goto_percent(n),
+        Action::Percent => {}
+        Action::NextBlock => app.next_block(n),
+        Action::PrevBlock => app.prev_block(n),
+        Action::ScreenTop => app.cursor_to_screen_top(n),
+        Action::ScreenMiddle => app.cursor_to_screen_middle(),
+        Action::ScreenBottom => app.cursor_to_screen_bottom(n),
+        Action::CursorTop => app.scroll_cursor_to(ScreenPosition::Top),
+        Action::CursorCenter => app.scroll_cursor_to(ScreenPosition::Center),
+        Action::CursorBottom => app.scroll_cursor_to(ScreenPosition::Bottom),
+        Action::NextHeading => app.next_heading(n),
+        Action::PrevHeading => app.prev_heading(n),
+        Action::Outline => app.toggle_outline(),
+
+        Action::NextLink => app.cycle_link(false),
+        Action::PrevLink => app.cycle_link(true),
+        // Without a selected link, Enter opens the tree's selection as before.
+        Action::FollowLink if app.focused_link.is_some() => app.follow_link(),
+        Action::FollowLink if app.show_tree => app.open_selected_file(),
+        Action::FollowLink => {}
+        Action::HistoryBack => app.history_back(),
+        Action::HistoryForward => app.history_forward(),
+
+        Action::SearchForward => app.start_search(true),
+        Action::SearchBackward => app.start_search(false),
+        Action::SearchNext => (0..n).for_each(|_| app.search_next(false)),
+        Action::SearchPrev => (0..n).for_each(|_| app.search_next(true)),
+        Action::Copy => app.copy_selection(false),
+        Action::CopySource => app.copy_selection(true),
+        Action::Edit => app.request_edit(),
+        Action::SourceView => app.toggle_source_view(),
+        Action::ToggleFolds => app.toggle_folds(),
+        Action::Footnote => app.jump_footnote(),
+        // Like vim, `3gt` goes to the third buffer; `3gT` goes back three.
+        Action::NextBuffer => match count {
+            Some(n) => app.switch_buffer(n - 1),
+            None => app.next_buffer(1),
+        },
+        Action::PrevBuffer => app.prev_buffer(n),
+        Action::BufferList => app.toggle_buffer_list(),
+        Action::Cancel => {
+            app.clear_search();
+            app.focused_link = None;
+        }
+        Action::Nop => {}
+    }
+    true
 }
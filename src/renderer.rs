@@ -1,41 +1,219 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
 use pulldown_cmark::{
-    Alignment, CodeBlockKind, Event as MdEvent, HeadingLevel, Options, Parser, Tag, TagEnd,
+    Alignment, BlockQuoteKind, CodeBlockKind, Event as MdEvent, HeadingLevel, LinkType, Options,
+    Parser, Tag, TagEnd, html,
 };
 use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
 };
+use unicode_width::UnicodeWidthChar;
 
 use crate::syntax::{get_highlighter, highlight_line};
 use syntect::easy::HighlightLines;
 
 use crate::app::SelectionRange;
+use crate::config::MarkdownStyles;
+
+/// A heading in the rendered document, for the outline and heading motions.
+#[derive(Clone, Debug)]
+pub struct HeadingEntry {
+    pub level: usize,
+    pub text: String,
+    /// Anchor used by `#fragment` links: the heading's `{#id}` or its GitHub-style slug.
+    pub anchor: String,
+    /// Rendered line the heading starts on.
+    pub line: usize,
+}
+
+/// A link in the rendered document, for Tab navigation and following.
+#[derive(Clone, Debug)]
+pub struct LinkEntry {
+    pub url: String,
+    /// `[[Page]]`-style wikilink, resolved to `Page.md`.
+    pub wiki: bool,
+    /// Rendered line and screen columns `[start_col, end_col)` of the link.
+    pub line: usize,
+    pub start_col: usize,
+    pub end_col: usize,
+}
+
+/// A footnote, numbered in order of first reference.
+#[derive(Clone, Debug)]
+pub struct FootnoteEntry {
+    /// Anchor of its definition, `fn-<label>`, which its references link to.
+    pub anchor: String,
+    /// Rendered lines of its definition in the footnotes section, if defined.
+    pub definition: Option<Range<usize>>,
+}
+
+/// Output of [`markdown_to_ratatui`]: one entry per screen row.
+pub struct Rendered {
+    pub text: Text<'static>,
+    /// Which markdown source each rendered line came from.
+    pub source_map: SourceMap,
+    pub headings: Vec<HeadingEntry>,
+    pub links: Vec<LinkEntry>,
+    /// First rendered line of each top-level block, list item and defined
+    /// term, for `{` / `}`.
+    pub blocks: Vec<usize>,
+    /// Footnotes in order of their numbers.
+    pub footnotes: Vec<FootnoteEntry>,
+    /// Words of text and code in the document.
+    pub word_count: usize,
+}
+
+/// A run of rendered lines and the markdown source bytes they show.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceEntry {
+    pub source: Range<usize>,
+    pub lines: Range<usize>,
+}
+
+/// Maps between byte ranges of the markdown source and rendered lines.
+/// Entries are in line order; blank lines have none.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    entries: Vec<SourceEntry>,
+}
+
+impl SourceMap {
+    /// Build from the source range of every rendered line, merging runs of
+    /// lines with the same range.
+    fn new(rows: Vec<Option<Range<usize>>>) -> Self {
+        let mut entries: Vec<SourceEntry> = Vec::new();
+        for (line, source) in rows.into_iter().enumerate() {
+            let Some(source) = source else {
+                continue;
+            };
+            match entries.last_mut() {
+                Some(last) if last.lines.end == line && last.source == source => {
+                    last.lines.end += 1;
+                }
+                _ => entries.push(SourceEntry {
+                    source,
+                    lines: line..line + 1,
+                }),
+            }
+        }
+        Self { entries }
+    }
+
+    /// Source bytes shown on rendered `line`.
+    pub fn source_of(&self, line: usize) -> Option<Range<usize>> {
+        let i = self.entries.partition_point(|e| e.lines.end <= line);
+        self.entries
+            .get(i)
+            .filter(|e| e.lines.contains(&line))
+            .map(|e| e.source.clone())
+    }
+
+    /// First rendered line showing source byte `offset`: the narrowest entry
+    /// containing it, or else the next entry after it.
+    pub fn line_of(&self, offset: usize) -> Option<usize> {
+        self.entries
+            .iter()
+            .filter(|e| e.source.contains(&offset))
+            .min_by_key(|e| (e.source.len(), e.lines.start))
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .filter(|e| e.source.start >= offset)
+                    .min_by_key(|e| (e.source.start, e.lines.start))
+            })
+            .map(|e| e.lines.start)
+    }
+
+    /// First rendered line showing source line `line` (counted from 1) of
+    /// `md`. Entries that merely enclose the line, such as the borders of
+    /// its code block, are only used if nothing else shows it.
+    pub fn line_of_source_line(&self, md: &str, line: usize) -> Option<usize> {
+        let start = match line {
+            0 | 1 => 0,
+            _ => md.match_indices('\n').nth(line - 2)?.0 + 1,
+        };
+        let end = md[start..].find('\n').map_or(md.len(), |i| start + i);
+        if start == end {
+            return self.line_of(start);
+        }
+
+        let (enclosing, showing): (Vec<&SourceEntry>, Vec<&SourceEntry>) = self
+            .entries
+            .iter()
+            .filter(|e| e.source.start < end && e.source.end > start)
+            .partition(|e| e.source.start < start && e.source.end > end);
+        showing
+            .iter()
+            .map(|e| e.lines.start)
+            .min()
+            .or_else(|| {
+                enclosing
+                    .iter()
+                    .min_by_key(|e| (e.source.len(), e.lines.start))
+                    .map(|e| e.lines.start)
+            })
+            .or_else(|| self.line_of(start))
+    }
+
+    /// Source line (counted from 1) of `md` shown on rendered `line`, or on
+    /// the next line after it that shows any.
+    pub fn source_line_at(&self, md: &str, line: usize) -> Option<usize> {
+        let i = self.entries.partition_point(|e| e.lines.end <= line);
+        Some(line_index(md, self.entries.get(i)?.source.start) + 1)
+    }
+}
 
+/// Index (counted from 0) of the source line holding byte `offset` of `md`.
+pub fn line_index(md: &str, offset: usize) -> usize {
+    let offset = offset.min(md.len());
+    md.as_bytes()[..offset]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+}
+
+/// Where the source of an inline event starts within an unwrapped line.
+struct SourceAnchor {
+    col: usize,
+    source: Range<usize>,
+    /// The event's text is shown exactly as written, so columns map to bytes.
+    exact: bool,
+}
+
+/// Render markdown wrapped to the inner width of `area_rect` (the preview
+/// block including its borders). With `unfold`, collapsed callouts show
+/// their content too.
 pub fn markdown_to_ratatui(
     md: &str,
     theme_name: &str,
-    selection: Option<SelectionRange>,
-    scroll_offset: u16,
-    _area_rect: ratatui::layout::Rect,
-) -> Text<'static> {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_TABLES);
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    options.insert(Options::ENABLE_TASKLISTS);
-    options.insert(Options::ENABLE_DEFINITION_LIST);
-    options.insert(Options::ENABLE_SUPERSCRIPT);
-    options.insert(Options::ENABLE_SUBSCRIPT);
-    options.insert(Options::ENABLE_WIKILINKS);
-    options.insert(Options::ENABLE_FOOTNOTES);
-    options.insert(Options::ENABLE_GFM);
-    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
-    options.insert(Options::ENABLE_MATH);
-    options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
-
-    let parser = Parser::new_ext(md, options);
+    styles: &MarkdownStyles,
+    area_rect: ratatui::layout::Rect,
+    unfold: bool,
+) -> Rendered {
+    let events: Vec<_> = Parser::new_ext(md, parser_options())
+        .into_offset_iter()
+        .collect();
+    let (events, footnote_labels) = move_footnotes(events);
+
+    // Text width inside the preview block's borders.
+    let width = area_rect.width.saturating_sub(2) as usize;
 
     let mut lines: Vec<Line> = Vec::new();
+    let mut line_sources: Vec<Option<Range<usize>>> = Vec::new();
+    // Width of the bullet / gutter / border prefix of a line, keyed by line
+    // index; wrapped rows of that line get the prefix repeated as a hanging indent.
+    let mut hanging_indents: HashMap<usize, usize> = HashMap::new();
     let mut spans: Vec<Span<'static>> = Vec::new();
+    // Source of the inline events in `spans`, and of the lines they ended up
+    // on, keyed by line index.
+    let mut spans_anchors: Vec<SourceAnchor> = Vec::new();
+    let mut line_anchors: HashMap<usize, Vec<SourceAnchor>> = HashMap::new();
+    // Unwrapped line index at which each top-level block, list item or
+    // defined term starts.
+    let mut block_starts: Vec<usize> = Vec::new();
+    let mut block_depth: usize = 0;
 
     let mut bold = false;
     let mut italic = false;
@@ -44,11 +222,32 @@ pub fn markdown_to_ratatui(
     let mut subscript = false;
     let mut in_heading = false;
     let mut heading_level = 0;
+    let mut heading_text = String::new();
+    let mut heading_id: Option<String> = None;
+    // Headings with the unwrapped line index they were pushed at.
+    let mut headings: Vec<HeadingEntry> = Vec::new();
     let mut in_code_block = false;
     let mut code_block_lang = String::new();
-    let mut in_list = false;
-    let mut list_depth: usize = 0;
+    // Enclosing blockquotes and list items, outermost first.
+    let mut containers: Vec<Container> = Vec::new();
+    // Blank lines since the last line with text, with the quote depth they
+    // were pushed at; they get gutters once more quoted text follows.
+    let mut pending_blanks: Vec<(usize, usize)> = Vec::new();
+    // Inside the `[!type] Title` line of a callout, already shown as its title.
+    let mut in_callout_header = false;
+    // Inside a collapsed callout: the depth of tags opened within it.
+    let mut folded_depth: Option<usize> = None;
+    // Next number and number width of each enclosing list (no number for
+    // bullet lists), outermost first.
+    let mut list_stack: Vec<(Option<u64>, usize)> = Vec::new();
+    let list_lengths = list_lengths(&events);
     let mut link_url = String::new();
+    // Links with positions relative to the unwrapped line they end up on.
+    let mut links: Vec<LinkEntry> = Vec::new();
+    // Links whose text is still in `spans`; columns are relative to `spans`.
+    let mut pending_links: Vec<usize> = Vec::new();
+    let mut link_start_col = 0;
+    let mut link_is_wiki = false;
     let mut current_highlighter: Option<HighlightLines<'static>> = None;
 
     // Table state
@@ -59,8 +258,115 @@ pub fn markdown_to_ratatui(
     let mut current_row: Vec<Vec<Span<'static>>> = Vec::new();
     let mut current_cell: Vec<Span<'static>> = Vec::new();
     let mut is_header_row = false;
+    let mut table_head_source: Option<Range<usize>> = None;
+    let mut word_count = 0;
+    let mut table_row_sources: Vec<Range<usize>> = Vec::new();
+    // Links in the cell being built, with columns relative to the cell, and
+    // links in the table with their row (0 for the header) and cell.
+    let mut cell_links: Vec<usize> = Vec::new();
+    let mut table_links: Vec<(usize, usize, usize)> = Vec::new();
+    // Table links placed on rendered lines by this event, which still need
+    // the width of any container prefix.
+    let mut placed_links: Vec<usize> = Vec::new();
+
+    let mut footnotes: Vec<FootnoteEntry> = footnote_labels
+        .iter()
+        .map(|label| FootnoteEntry {
+            anchor: footnote_anchor(label),
+            definition: None,
+        })
+        .collect();
+    let mut in_footnotes = false;
+    // In a definition list term, or the first line of the current definition.
+    let mut in_term = false;
+    let mut definition_start: Option<usize> = None;
+    // Index of the footnote being defined, and its first line.
+    let mut footnote_start = (0, 0);
+
+    for (event, source_range) in events {
+        if let Some(depth) = folded_depth {
+            match &event {
+                MdEvent::Start(_) => folded_depth = Some(depth + 1),
+                MdEvent::End(_) if depth > 0 => folded_depth = Some(depth - 1),
+                MdEvent::End(_) => folded_depth = None,
+                _ => {}
+            }
+            if folded_depth.is_some() {
+                continue;
+            }
+        }
+        if in_callout_header {
+            match &event {
+                MdEvent::Start(Tag::Paragraph) => {}
+                MdEvent::End(TagEnd::Paragraph) => in_callout_header = false,
+                MdEvent::SoftBreak | MdEvent::HardBreak => {
+                    in_callout_header = false;
+                    continue;
+                }
+                _ => continue,
+            }
+        }
+
+        let lines_before = lines.len();
+        let spans_before = spans.len();
+        let spans_width: usize = spans.iter().map(|s| s.width()).sum();
+        // Lines pushed by this event that came from part of its source range.
+        let mut line_parts: Vec<(usize, Range<usize>)> = Vec::new();
+
+        if let MdEvent::Text(text) | MdEvent::Code(text) = &event {
+            word_count += text.split_whitespace().count();
+        }
+
+        // Text of a tight list item goes on its own line before a block
+        // nested in the item.
+        if let MdEvent::Start(tag) = &event
+            && is_block(tag)
+            && !spans.is_empty()
+            && !in_table
+        {
+            lines.push(Line::from(std::mem::take(&mut spans)));
+            let depth = containers.len();
+            prefix_lines(
+                &mut lines,
+                lines_before..lines_before + 1,
+                &mut containers,
+                depth,
+                &mut pending_blanks,
+                &mut hanging_indents,
+            );
+        }
+        // Definitions were moved to the end; the first one starts the
+        // footnotes section (outside any container).
+        if matches!(event, MdEvent::Start(Tag::FootnoteDefinition(_))) && !in_footnotes {
+            in_footnotes = true;
+            if lines.last().is_some_and(|l| l.width() > 0) {
+                lines.push(Line::default());
+            }
+            lines.push(Line::from(Span::styled("─".repeat(width), styles.rule)));
+            headings.push(HeadingEntry {
+                level: 2,
+                text: "Footnotes".to_string(),
+                anchor: unique_anchor("Footnotes", &headings),
+                line: lines.len(),
+            });
+            lines.push(Line::from(Span::styled("Footnotes", styles.heading(2))));
+            lines.push(Line::default());
+        }
+        let prefixed = lines.len();
+        let mut containers_before = containers.clone();
+
+        match &event {
+            MdEvent::Start(tag) if is_block(tag) => {
+                if block_depth == 0 || matches!(tag, Tag::Item | Tag::DefinitionListTitle) {
+                    block_starts.push(lines.len());
+                }
+                block_depth += 1;
+            }
+            MdEvent::End(tag) if is_block_end(tag) => block_depth -= 1,
+            MdEvent::Rule if block_depth == 0 => block_starts.push(lines.len()),
+            _ => {}
+        }
 
-    for event in parser {
         match event {
             MdEvent::Start(Tag::Strong) => bold = true,
             MdEvent::End(TagEnd::Strong) => bold = false,
@@ -82,8 +388,10 @@ pub fn markdown_to_ratatui(
             MdEvent::End(TagEnd::Subscript) => subscript = false,
 
             // Headings
-            MdEvent::Start(Tag::Heading { level, .. }) => {
+            MdEvent::Start(Tag::Heading { level, id, .. }) => {
                 in_heading = true;
+                heading_text.clear();
+                heading_id = id.map(|id| id.to_string());
                 heading_level = match level {
                     HeadingLevel::H1 => 1,
                     HeadingLevel::H2 => 2,
@@ -93,25 +401,15 @@ pub fn markdown_to_ratatui(
                     HeadingLevel::H6 => 6,
                 };
 
-                if heading_level == 1 {
-                    spans.push(Span::raw("\n"));
+                if heading_level == 1 && lines.last().is_some_and(|l| l.width() > 0) {
+                    lines.push(Line::default());
                 }
 
                 // Add # symbols for H2-H6
                 if heading_level >= 2 {
-                    let symbol_color = match heading_level {
-                        2 => Color::Rgb(135, 206, 250),
-                        3 => Color::Rgb(255, 182, 193),
-                        4 => Color::Rgb(144, 238, 144),
-                        5 => Color::Rgb(221, 160, 221),
-                        _ => Color::Rgb(255, 218, 185),
-                    };
-
                     spans.push(Span::styled(
                         format!("{} ", "#".repeat(heading_level)),
-                        Style::default()
-                            .fg(symbol_color)
-                            .add_modifier(Modifier::BOLD),
+                        styles.heading(heading_level),
                     ));
                 }
             }
@@ -119,12 +417,23 @@ pub fn markdown_to_ratatui(
             MdEvent::End(TagEnd::Heading(_)) => {
                 in_heading = false;
                 if !spans.is_empty() {
-                    lines.push(Line::from(spans.drain(..).collect::<Vec<_>>()));
+                    let text = std::mem::take(&mut heading_text).trim().to_string();
+                    let anchor = heading_id
+                        .take()
+                        .unwrap_or_else(|| unique_anchor(&text, &headings));
+                    headings.push(HeadingEntry {
+                        level: heading_level,
+                        text,
+                        anchor,
+                        line: lines.len(),
+                    });
+                    lines.push(Line::from(std::mem::take(&mut spans)));
 
                     if heading_level == 1 {
+                        let gutters: usize = containers.iter().map(|c| c.prefix.width()).sum();
                         lines.push(Line::from(vec![Span::styled(
-                            "━".repeat(70),
-                            Style::default().fg(Color::Rgb(135, 206, 250)),
+                            "━".repeat(width.saturating_sub(gutters)),
+                            styles.h1_underline,
                         )]));
                     }
 
@@ -142,19 +451,14 @@ pub fn markdown_to_ratatui(
                         let lang_str = lang.to_string();
                         let actual_lang = if !lang_str.is_empty() {
                             lines.push(Line::from(vec![
-                                Span::styled(" ┌─ ", Style::default().fg(Color::DarkGray)),
-                                Span::styled(
-                                    lang_str.clone(),
-                                    Style::default()
-                                        .fg(Color::Rgb(255, 182, 193))
-                                        .add_modifier(Modifier::ITALIC),
-                                ),
+                                Span::styled(" ┌─ ", styles.code_border),
+                                Span::styled(lang_str.clone(), styles.code_language),
                             ]));
                             lang_str
                         } else {
                             lines.push(Line::from(vec![Span::styled(
                                 " ┌─────",
-                                Style::default().fg(Color::DarkGray),
+                                styles.code_border,
                             )]));
                             String::new()
                         };
@@ -168,7 +472,7 @@ pub fn markdown_to_ratatui(
                     CodeBlockKind::Indented => {
                         lines.push(Line::from(vec![Span::styled(
                             " ┌─────",
-                            Style::default().fg(Color::DarkGray),
+                            styles.code_border,
                         )]));
                         String::new()
                     }
@@ -181,105 +485,189 @@ pub fn markdown_to_ratatui(
                 current_highlighter = None;
                 lines.push(Line::from(vec![Span::styled(
                     " └─────",
-                    Style::default().fg(Color::DarkGray),
+                    styles.code_border,
                 )]));
                 lines.push(Line::default());
             }
 
-            // Lists
-            MdEvent::Start(Tag::List(_)) => {
-                in_list = true;
-                list_depth += 1;
-                if list_depth == 1 {
-                    lines.push(Line::default());
+            // Lists; numbers are right-aligned to the widest one in the list.
+            MdEvent::Start(Tag::List(start)) => {
+                if list_stack.is_empty() {
+                    push_separator(&mut lines, &pending_blanks);
                 }
+                let width = start.map_or(0, |start| {
+                    let count = list_lengths.get(&source_range.start).copied().unwrap_or(1);
+                    (start + count.saturating_sub(1)).to_string().len()
+                });
+                list_stack.push((start, width));
             }
 
             MdEvent::End(TagEnd::List(_)) => {
-                list_depth -= 1;
-                if list_depth == 0 {
-                    in_list = false;
+                list_stack.pop();
+                if list_stack.is_empty() && lines.last().is_some_and(|l| l.width() > 0) {
                     lines.push(Line::default());
                 }
             }
 
-            MdEvent::Start(Tag::Item) => {}
+            // List items indent their lines below, with the bullet or number
+            // on the first one.
+            MdEvent::Start(Tag::Item) => {
+                let depth = list_stack.len();
+                let marker = match list_stack.last_mut() {
+                    Some((Some(number), width)) => {
+                        *number += 1;
+                        format!(" {:>w$}. ", *number - 1, w = *width)
+                    }
+                    _ => format!(" {} ", BULLETS[depth.saturating_sub(1) % BULLETS.len()]),
+                };
+                containers.push(Container::item(Span::styled(
+                    marker,
+                    Style::default().fg(styles.bullet(depth)),
+                )));
+            }
 
             MdEvent::End(TagEnd::Item) => {
                 if !spans.is_empty() {
-                    let indent = "  ".repeat(list_depth.saturating_sub(1));
-                    let bullet_colors = [
-                        Color::Rgb(255, 182, 193),
-                        Color::Rgb(173, 216, 230),
-                        Color::Rgb(255, 218, 185),
-                    ];
-                    let color = bullet_colors[(list_depth - 1) % bullet_colors.len()];
-
-                    let mut item_spans = vec![
-                        Span::raw(indent),
-                        Span::styled(" • ", Style::default().fg(color)),
-                    ];
-                    item_spans.extend(spans.drain(..));
-                    lines.push(Line::from(item_spans));
+                    lines.push(Line::from(std::mem::take(&mut spans)));
                 }
+                containers.pop();
             }
 
             // Task list item
             MdEvent::TaskListMarker(checked) => {
                 let checkbox = if checked {
-                    Span::styled("[✓] ", Style::default().fg(Color::LightGreen))
+                    Span::styled("[✓] ", styles.task_done)
                 } else {
-                    Span::styled("[ ] ", Style::default().fg(Color::DarkGray))
+                    Span::styled("[ ] ", styles.task_open)
                 };
                 spans.insert(0, checkbox);
             }
 
-            // Blockquote
-            MdEvent::Start(Tag::BlockQuote(_)) => {
-                lines.push(Line::default());
+            // Blockquote; the gutters are added to its lines below. Alerts
+            // and callouts are boxed in their color, with the title in the
+            // top border.
+            MdEvent::Start(Tag::BlockQuote(kind)) => {
+                let callout = match kind {
+                    Some(kind) => Some(Callout {
+                        kind,
+                        title: None,
+                        folded: None,
+                    }),
+                    None => callout_header(&md[source_range.clone()]),
+                };
+                push_separator(&mut lines, &pending_blanks);
+                match callout {
+                    Some(callout) => {
+                        let (style, icon, name) = alert_look(callout.kind, styles);
+                        containers.push(Container::callout(style));
+                        let marker = match callout.folded {
+                            Some(true) if !unfold => " ▸",
+                            Some(_) => " ▾",
+                            None => "",
+                        };
+                        let title_style = style.add_modifier(Modifier::BOLD);
+                        let mut title = vec![Span::styled(format!(" {} ", icon), title_style)];
+                        match &callout.title {
+                            Some(text) => title.extend(inline_spans(text, title_style, styles)),
+                            None => title.push(Span::styled(name, title_style)),
+                        }
+                        title.push(Span::styled(format!("{} ", marker), title_style));
+                        let gutters: usize = containers.iter().map(|c| c.prefix.width()).sum();
+                        let title_width: usize = title.iter().map(Span::width).sum();
+                        title.push(Span::styled(
+                            "━".repeat(width.saturating_sub(gutters + title_width)),
+                            style,
+                        ));
+                        lines.push(Line::from(title));
+                        if callout.folded == Some(true) && !unfold {
+                            folded_depth = Some(0);
+                        } else {
+                            in_callout_header = kind.is_none();
+                        }
+                    }
+                    None => containers.push(Container::quote(styles.quote)),
+                }
             }
 
             MdEvent::End(TagEnd::BlockQuote(_)) => {
+                let quote = containers.pop();
                 if !spans.is_empty() {
-                    let quote_text: Vec<Span> = spans.drain(..).collect();
-                    let mut quote_line = vec![Span::styled(
-                        " ┃ ",
-                        Style::default()
-                            .fg(Color::Rgb(255, 182, 193))
-                            .add_modifier(Modifier::BOLD),
-                    )];
-                    quote_line.extend(quote_text);
-                    lines.push(Line::from(quote_line));
+                    lines.push(Line::from(std::mem::take(&mut spans)));
                 }
-                lines.push(Line::default());
+                // A callout's box closes with a bottom border, which starts
+                // with a corner in place of the gutter.
+                if let Some(corner) = quote.and_then(|q| q.bottom) {
+                    let gutters: usize = containers_before.iter().map(|c| c.prefix.width()).sum();
+                    let border = Line::from(Span::styled(
+                        "━".repeat(width.saturating_sub(gutters)),
+                        corner.style,
+                    ));
+                    if let Some(quote) = containers_before.last_mut() {
+                        quote.marker = Some(corner);
+                    }
+                    // It takes the place of the blank line after the last block.
+                    match pending_blanks.last() {
+                        Some(&(blank, _)) if blank + 1 == lines.len() => {
+                            pending_blanks.pop();
+                            lines[blank] = border;
+                            line_sources[blank] = Some(source_range.clone());
+                            prefix_lines(
+                                &mut lines,
+                                blank..blank + 1,
+                                &mut containers_before,
+                                0,
+                                &mut pending_blanks,
+                                &mut hanging_indents,
+                            );
+                        }
+                        _ => lines.push(border),
+                    }
+                }
+                push_separator(&mut lines, &pending_blanks);
             }
 
             // Links
-            MdEvent::Start(Tag::Link { dest_url, .. }) => {
+            MdEvent::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) => {
                 link_url = dest_url.to_string();
+                link_is_wiki = matches!(link_type, LinkType::WikiLink { .. });
+                let inline = if in_table { &current_cell } else { &spans };
+                link_start_col = inline.iter().map(|s| s.width()).sum();
             }
 
-            MdEvent::End(TagEnd::Link) => {
-                if !link_url.is_empty() {
-                    let link_span = Span::styled(
-                        format!(" (🔗 {})", link_url),
-                        Style::default()
-                            .fg(Color::Rgb(135, 206, 250))
-                            .add_modifier(Modifier::DIM),
-                    );
-                    if in_table {
-                        current_cell.push(link_span);
-                    } else {
-                        spans.push(link_span);
-                    }
-                    link_url.clear();
+            MdEvent::End(TagEnd::Link) if !link_url.is_empty() => {
+                let link_span = Span::styled(format!(" (🔗 {})", link_url), styles.link);
+                if in_table {
+                    current_cell.push(link_span);
+                    // Tables are laid out at the end; place it then.
+                    cell_links.push(links.len());
+                    links.push(LinkEntry {
+                        url: link_url.clone(),
+                        wiki: link_is_wiki,
+                        line: 0,
+                        start_col: link_start_col,
+                        end_col: current_cell.iter().map(|s| s.width()).sum(),
+                    });
+                } else {
+                    spans.push(link_span);
+                    pending_links.push(links.len());
+                    links.push(LinkEntry {
+                        url: link_url.clone(),
+                        wiki: link_is_wiki,
+                        line: 0,
+                        start_col: link_start_col,
+                        end_col: spans.iter().map(|s| s.width()).sum(),
+                    });
                 }
+                link_url.clear();
             }
 
             // Images
             MdEvent::Start(Tag::Image { dest_url, .. }) => {
-                let image_span =
-                    Span::styled("🖼️  ", Style::default().fg(Color::Rgb(255, 182, 193)));
+                let image_span = Span::styled("🖼️  ", styles.image);
                 if in_table {
                     current_cell.push(image_span);
                 } else {
@@ -288,120 +676,136 @@ pub fn markdown_to_ratatui(
                 link_url = dest_url.to_string();
             }
 
-            MdEvent::End(TagEnd::Image) => {
-                if !link_url.is_empty() {
-                    let url_span = Span::styled(
-                        format!(" ({})", link_url),
-                        Style::default()
-                            .fg(Color::Rgb(135, 206, 250))
-                            .add_modifier(Modifier::DIM),
-                    );
-                    if in_table {
-                        current_cell.push(url_span);
-                    } else {
-                        spans.push(url_span);
-                    }
-                    link_url.clear();
+            MdEvent::End(TagEnd::Image) if !link_url.is_empty() => {
+                let url_span = Span::styled(format!(" ({})", link_url), styles.link);
+                if in_table {
+                    current_cell.push(url_span);
+                } else {
+                    spans.push(url_span);
                 }
+                link_url.clear();
+            }
+
+            // Paragraphs of a definition are only separated from each other.
+            MdEvent::Start(Tag::Paragraph)
+                if definition_start.is_some_and(|start| lines.len() > start) =>
+            {
+                lines.push(Line::default());
             }
 
-            MdEvent::End(TagEnd::Paragraph) => {
-                if !spans.is_empty() && !in_list && !in_table {
-                    lines.push(Line::from(spans.drain(..).collect::<Vec<_>>()));
+            MdEvent::End(TagEnd::Paragraph) if !spans.is_empty() && !in_table => {
+                lines.push(Line::from(std::mem::take(&mut spans)));
+                if definition_start.is_none() {
                     lines.push(Line::default());
                 }
             }
 
+            // Definition lists: bold terms, with their definitions indented
+            // below them.
+            MdEvent::Start(Tag::DefinitionList) | MdEvent::End(TagEnd::DefinitionList)
+                if lines.last().is_some_and(|l| l.width() > 0) =>
+            {
+                lines.push(Line::default());
+            }
+
+            MdEvent::Start(Tag::DefinitionListTitle) => {
+                in_term = true;
+                if lines.last().is_some_and(|l| l.width() > 0) {
+                    lines.push(Line::default());
+                }
+            }
+
+            MdEvent::End(TagEnd::DefinitionListTitle) => {
+                in_term = false;
+                if !spans.is_empty() {
+                    lines.push(Line::from(std::mem::take(&mut spans)));
+                }
+            }
+
+            MdEvent::Start(Tag::DefinitionListDefinition) => {
+                containers.push(Container::item(Span::styled(
+                    "  ↳ ",
+                    Style::default().fg(styles.bullet(1)),
+                )));
+                definition_start = Some(lines.len());
+            }
+
+            MdEvent::End(TagEnd::DefinitionListDefinition) => {
+                if !spans.is_empty() {
+                    lines.push(Line::from(std::mem::take(&mut spans)));
+                }
+                containers.pop();
+                definition_start = None;
+            }
+
             // Text
             MdEvent::Text(text) => {
                 if in_code_block {
-                    for line in text.split('\n') {
+                    // Code shown as written maps line by line onto its source.
+                    let exact = md[source_range.clone()] == *text;
+                    let mut line_start = source_range.start;
+                    // A trailing newline ends the last line rather than starting a new one.
+                    for line in text.strip_suffix('\n').unwrap_or(&text).split('\n') {
                         if !spans.is_empty() {
-                            lines.push(Line::from(spans.drain(..).collect::<Vec<_>>()));
+                            lines.push(Line::from(std::mem::take(&mut spans)));
+                        }
+                        if exact {
+                            line_parts.push((lines.len(), line_start..line_start + line.len()));
+                            line_start += line.len() + 1;
                         }
 
-                        let mut line_spans =
-                            vec![Span::styled(" │ ", Style::default().fg(Color::DarkGray))];
+                        let mut line_spans = vec![Span::styled(" │ ", styles.code_border)];
 
                         if let Some(highlighter) = &mut current_highlighter {
                             line_spans.extend(highlight_line(line, highlighter));
                         } else {
-                            line_spans.push(Span::styled(
-                                line.to_string(),
-                                Style::default().fg(Color::White),
-                            ));
+                            line_spans.push(Span::styled(line.to_string(), styles.code_block));
                         }
 
+                        hanging_indents.insert(lines.len(), 3);
                         lines.push(Line::from(line_spans));
                     }
                 } else {
-                    let mut style = Style::default().fg(Color::White);
-
-                    if in_heading && heading_level == 1 {
-                        style = Style::default()
-                            .fg(Color::White)
-                            .bg(Color::Rgb(65, 105, 225))
-                            .add_modifier(Modifier::BOLD);
-                    } else if in_heading {
-                        let color = match heading_level {
-                            2 => Color::Rgb(135, 206, 250),
-                            3 => Color::Rgb(255, 182, 193),
-                            4 => Color::Rgb(144, 238, 144),
-                            5 => Color::Rgb(221, 160, 221),
-                            _ => Color::Rgb(255, 218, 185),
-                        };
-                        style = style.fg(color).add_modifier(Modifier::BOLD);
-                    } else {
-                        // Build modifiers and colors based on active formatting
-                        let mut modifiers = Modifier::empty();
-                        let mut text_color = Color::White;
-
-                        // Apply strikethrough modifier (but don't override color yet)
-                        if strikethrough {
-                            modifiers |= Modifier::CROSSED_OUT;
-                        }
+                    if in_heading {
+                        heading_text.push_str(&text);
+                    }
+                    let mut style = styles.text;
 
-                        // Apply bold/italic colors (strikethrough can coexist)
-                        if bold {
-                            modifiers |= Modifier::BOLD;
-                            if !strikethrough {
-                                text_color = Color::Rgb(255, 218, 185);
-                            } else {
-                                // When strikethrough is active, use a muted version of bold color
-                                text_color = Color::Rgb(169, 169, 169);
-                            }
+                    if in_heading {
+                        style = style.patch(styles.heading(heading_level));
+                    } else {
+                        // Nested formatting keeps the outer color and adds the
+                        // inner modifiers; strikethrough always mutes the color.
+                        let plain = !bold && !italic && !strikethrough;
+                        if bold || in_term {
+                            style = style.patch(styles.strong);
                         }
                         if italic {
-                            modifiers |= Modifier::ITALIC;
-                            if !bold {
-                                if !strikethrough {
-                                    text_color = Color::Rgb(221, 160, 221);
-                                } else {
-                                    // When strikethrough is active, use gray
-                                    text_color = Color::Rgb(169, 169, 169);
-                                }
-                            }
+                            style = if bold {
+                                style.patch(Style {
+                                    fg: None,
+                                    ..styles.emphasis
+                                })
+                            } else {
+                                style.patch(styles.emphasis)
+                            };
                         }
-
-                        // If only strikethrough (no bold/italic), use gray
-                        if strikethrough && !bold && !italic {
-                            text_color = Color::Rgb(169, 169, 169);
+                        if strikethrough {
+                            style = style.patch(styles.strikethrough);
                         }
 
                         if superscript {
-                            modifiers |= Modifier::DIM;
-                            if !strikethrough && !bold && !italic {
-                                text_color = Color::LightCyan;
+                            style = style.add_modifier(Modifier::DIM);
+                            if plain {
+                                style = style.fg(Color::LightCyan);
                             }
                         }
                         if subscript {
-                            modifiers |= Modifier::DIM;
-                            if !strikethrough && !bold && !italic && !superscript {
-                                text_color = Color::LightMagenta;
+                            style = style.add_modifier(Modifier::DIM);
+                            if plain && !superscript {
+                                style = style.fg(Color::LightMagenta);
                             }
                         }
-
-                        style = style.fg(text_color).add_modifier(modifiers);
                     }
 
                     // Format text for superscript/subscript display
@@ -424,13 +828,10 @@ pub fn markdown_to_ratatui(
 
             // Inline code
             MdEvent::Code(code) => {
-                let code_span = Span::styled(
-                    format!(" {} ", code),
-                    Style::default()
-                        .fg(Color::Rgb(220, 80, 80))
-                        .bg(Color::Rgb(60, 60, 60))
-                        .add_modifier(Modifier::BOLD),
-                );
+                if in_heading {
+                    heading_text.push_str(&code);
+                }
+                let code_span = Span::styled(format!(" {} ", code), styles.code);
                 if in_table {
                     current_cell.push(code_span);
                 } else {
@@ -440,12 +841,7 @@ pub fn markdown_to_ratatui(
 
             // Math
             MdEvent::InlineMath(text) => {
-                let math_span = Span::styled(
-                    format!(" ${}$ ", text),
-                    Style::default()
-                        .fg(Color::Rgb(144, 238, 144))
-                        .add_modifier(Modifier::ITALIC),
-                );
+                let math_span = Span::styled(format!(" ${}$ ", text), styles.math);
                 if in_table {
                     current_cell.push(math_span);
                 } else {
@@ -454,31 +850,63 @@ pub fn markdown_to_ratatui(
             }
             MdEvent::DisplayMath(text) => {
                 if !spans.is_empty() {
-                    lines.push(Line::from(spans.drain(..).collect::<Vec<_>>()));
+                    lines.push(Line::from(std::mem::take(&mut spans)));
                 }
                 lines.push(Line::default());
                 lines.push(Line::from(Span::styled(
                     format!(" $${}$$ ", text),
-                    Style::default()
-                        .fg(Color::Rgb(144, 238, 144))
-                        .add_modifier(Modifier::ITALIC),
+                    styles.math,
                 )));
                 lines.push(Line::default());
             }
 
-            // Footnotes (basic handling)
+            // Footnotes; references link to their definition.
             MdEvent::FootnoteReference(label) => {
-                let foot_span = Span::styled(
-                    format!("[^{}]", label),
-                    Style::default()
-                        .fg(Color::LightCyan)
-                        .add_modifier(Modifier::DIM),
-                );
+                let number = footnote_number(&footnote_labels, &label);
+                let foot_span = Span::styled(format!("[{}]", number), styles.footnote);
+                let url = format!("#{}", footnote_anchor(&label));
                 if in_table {
+                    let start_col = current_cell.iter().map(|s| s.width()).sum();
                     current_cell.push(foot_span);
+                    cell_links.push(links.len());
+                    links.push(LinkEntry {
+                        url,
+                        wiki: false,
+                        line: 0,
+                        start_col,
+                        end_col: current_cell.iter().map(|s| s.width()).sum(),
+                    });
                 } else {
+                    let start_col = spans.iter().map(|s| s.width()).sum();
                     spans.push(foot_span);
+                    pending_links.push(links.len());
+                    links.push(LinkEntry {
+                        url,
+                        wiki: false,
+                        line: 0,
+                        start_col,
+                        end_col: spans.iter().map(|s| s.width()).sum(),
+                    });
+                }
+            }
+
+            MdEvent::Start(Tag::FootnoteDefinition(label)) => {
+                let number = footnote_number(&footnote_labels, &label);
+                let digits = footnote_labels.len().to_string().len();
+                containers.push(Container::item(Span::styled(
+                    format!(" {:>w$} ", format!("[{}]", number), w = digits + 2),
+                    styles.footnote,
+                )));
+                footnote_start = (number - 1, lines.len());
+            }
+
+            MdEvent::End(TagEnd::FootnoteDefinition) => {
+                if !spans.is_empty() {
+                    lines.push(Line::from(std::mem::take(&mut spans)));
                 }
+                containers.pop();
+                let (index, start) = footnote_start;
+                footnotes[index].definition = Some(start..lines.len());
             }
 
             MdEvent::SoftBreak => {
@@ -494,15 +922,16 @@ pub fn markdown_to_ratatui(
                 if in_table {
                     current_cell.push(Span::raw(" "));
                 } else if !spans.is_empty() {
-                    lines.push(Line::from(spans.drain(..).collect::<Vec<_>>()));
+                    lines.push(Line::from(std::mem::take(&mut spans)));
                 }
             }
 
             MdEvent::Rule => {
-                lines.push(Line::default());
+                let gutters: usize = containers.iter().map(|c| c.prefix.width()).sum();
+                push_separator(&mut lines, &pending_blanks);
                 lines.push(Line::from(vec![Span::styled(
-                    "─".repeat(70),
-                    Style::default().fg(Color::Rgb(255, 182, 193)),
+                    "─".repeat(width.saturating_sub(gutters)),
+                    styles.rule,
                 )]));
                 lines.push(Line::default());
             }
@@ -513,6 +942,9 @@ pub fn markdown_to_ratatui(
                 table_alignments = alignments;
                 table_headers.clear();
                 table_rows.clear();
+                table_head_source = None;
+                table_row_sources.clear();
+                table_links.clear();
                 current_row.clear();
                 current_cell.clear();
                 is_header_row = false;
@@ -520,7 +952,39 @@ pub fn markdown_to_ratatui(
 
             MdEvent::End(TagEnd::Table) => {
                 in_table = false;
-                render_table(&mut lines, &table_headers, &table_rows, &table_alignments);
+                let gutters: usize = containers.iter().map(|c| c.prefix.width()).sum();
+                let cell_starts = render_table(
+                    &mut lines,
+                    &table_headers,
+                    &table_rows,
+                    &table_alignments,
+                    styles,
+                    width.saturating_sub(gutters),
+                );
+                // Below a blank line come the header, the separator and the rows.
+                for (index, row, cell) in table_links.drain(..) {
+                    let Some(&start) = cell_starts.get(row).and_then(|r| r.get(cell)) else {
+                        continue;
+                    };
+                    let link = &mut links[index];
+                    link.line = lines_before + if row == 0 { 1 } else { row + 2 };
+                    link.start_col += start;
+                    link.end_col += start;
+                    placed_links.push(index);
+                }
+                // After a blank line, the header and separator show the head
+                // and each body row its own source line.
+                if lines.len() > lines_before {
+                    let head = table_head_source.take();
+                    let sources = [head.clone(), head]
+                        .into_iter()
+                        .chain(table_row_sources.drain(..).map(Some));
+                    for (i, source) in (lines_before + 1..).zip(sources) {
+                        if let Some(source) = source {
+                            line_parts.push((i, source));
+                        }
+                    }
+                }
             }
 
             MdEvent::Start(Tag::TableHead) => {
@@ -528,6 +992,7 @@ pub fn markdown_to_ratatui(
             }
 
             MdEvent::End(TagEnd::TableHead) => {
+                table_head_source = Some(source_range.clone());
                 // If we're ending the header section and haven't stored headers yet, store them now
                 // This handles the case where headers might not have been stored in End(TableRow)
                 if is_header_row && table_headers.is_empty() && !current_row.is_empty() {
@@ -545,8 +1010,10 @@ pub fn markdown_to_ratatui(
                     // Always store header row - in standard markdown there's only one header row
                     // Even if cells are empty, we need to store the structure
                     table_headers = std::mem::take(&mut current_row);
+                    table_head_source = Some(source_range.clone());
                 } else if !current_row.is_empty() {
                     table_rows.push(std::mem::take(&mut current_row));
+                    table_row_sources.push(source_range.clone());
                 }
             }
 
@@ -555,134 +1022,883 @@ pub fn markdown_to_ratatui(
             }
 
             MdEvent::End(TagEnd::TableCell) => {
+                let text: String = current_cell.iter().map(|s| s.content.as_ref()).collect();
+                let leading = text.len() - text.trim_start().len();
+                let row = if is_header_row {
+                    0
+                } else {
+                    table_rows.len() + 1
+                };
+                for index in cell_links.drain(..) {
+                    let link = &mut links[index];
+                    link.start_col -= leading.min(link.start_col);
+                    link.end_col -= leading.min(link.end_col);
+                    table_links.push((index, row, current_row.len()));
+                }
                 let trimmed_cell = trim_spans(std::mem::take(&mut current_cell));
                 current_row.push(trimmed_cell);
             }
 
             _ => {}
         }
+
+        // Lines flushed by this event came from its source range, or the
+        // part of it noted above.
+        for (i, line) in lines.iter().enumerate().skip(lines_before) {
+            line_sources.push((i >= prefixed && line.width() > 0).then(|| source_range.clone()));
+        }
+        for (i, source) in line_parts {
+            line_sources[i] = Some(source);
+        }
+
+        // Every line inside blockquotes and list items gets their prefixes.
+        // An event that opens or closes one puts its text inside and its
+        // blank lines outside.
+        let outer_depth = containers.len().min(containers_before.len());
+        let mut used = if containers.len() > containers_before.len() {
+            containers.clone()
+        } else {
+            containers_before
+        };
+        let pushed = prefixed..lines.len();
+        prefix_lines(
+            &mut lines,
+            pushed,
+            &mut used,
+            outer_depth,
+            &mut pending_blanks,
+            &mut hanging_indents,
+        );
+        // Markers drawn on this event's lines are used up.
+        for (container, used) in containers.iter_mut().zip(used) {
+            container.marker = used.marker;
+        }
+        let prefix_width: usize = containers.iter().map(|c| c.prefix.width()).sum();
+        for index in placed_links.drain(..) {
+            links[index].start_col += prefix_width;
+            links[index].end_col += prefix_width;
+        }
+        for (_, depth) in &mut pending_blanks {
+            *depth = (*depth).min(containers.len());
+        }
+
+        // Inline content is anchored at the column it starts at in `spans`;
+        // when `spans` is flushed, its anchors move to the line it became
+        // (the first one with text that this event pushed).
+        if spans.len() > spans_before {
+            let exact = spans.len() == spans_before + 1
+                && spans[spans_before].content == md[source_range.clone()];
+            spans_anchors.push(SourceAnchor {
+                col: spans_width,
+                source: source_range.clone(),
+                exact,
+            });
+        } else if spans_before > 0
+            && spans.is_empty()
+            && let Some(i) = (lines_before..lines.len()).find(|&i| lines[i].width() > 0)
+        {
+            let offset = lines[i].width().saturating_sub(spans_width);
+            let mut anchors = std::mem::take(&mut spans_anchors);
+            for anchor in &mut anchors {
+                anchor.col += offset;
+            }
+            line_sources[i] = anchored_source(md, &anchors, 0..usize::MAX);
+            line_anchors.insert(i, anchors);
+        }
+
+        // When `spans` is flushed, it becomes the tail of the first line pushed
+        // (after any list bullet or quote gutter), so shift pending links onto it.
+        if !pending_links.is_empty() && spans.is_empty() && lines.len() > lines_before {
+            let offset = lines[lines_before].width().saturating_sub(spans_width);
+            for index in pending_links.drain(..) {
+                let link = &mut links[index];
+                link.line = lines_before;
+                link.start_col += offset;
+                link.end_col += offset;
+            }
+        }
     }
 
     if !spans.is_empty() {
+        for index in pending_links.drain(..) {
+            links[index].line = lines.len();
+        }
+        line_sources.push(anchored_source(md, &spans_anchors, 0..usize::MAX));
+        line_anchors.insert(lines.len(), spans_anchors);
         lines.push(Line::from(spans));
     }
+    line_sources.resize(lines.len(), None);
+
+    // Blocks start on their first line with text (not just quote gutters).
+    let block_starts: Vec<usize> = block_starts
+        .into_iter()
+        .filter_map(|i| (i..lines.len()).find(|&j| line_sources[j].is_some()))
+        .collect();
+
+    // Wrap to the preview width ourselves so that every rendered line is
+    // exactly one screen row, which is what selection and scrolling work on.
+    let mut wrapped: Vec<Line<'static>> = Vec::with_capacity(lines.len());
+    let mut wrapped_sources = Vec::with_capacity(lines.len());
+    // First wrapped row of each unwrapped line, and the column each row starts at.
+    let mut first_rows = Vec::with_capacity(lines.len());
+    let mut row_starts = Vec::with_capacity(lines.len());
+    for (i, (line, source)) in lines.into_iter().zip(line_sources).enumerate() {
+        first_rows.push(wrapped.len());
+        let indent = hanging_indents.get(&i).copied().unwrap_or(0);
+        let anchors = line_anchors.remove(&i).unwrap_or_default();
+        let rows = wrap_line(line, width, indent);
+        let row_ends: Vec<usize> = (rows.iter().skip(1).map(|r| r.start_col))
+            .chain([usize::MAX])
+            .collect();
+        for (row, end) in rows.into_iter().zip(row_ends) {
+            // Rows of a wrapped line show only part of its source.
+            let row_source = match anchors.len() {
+                0 => source.clone(),
+                _ => anchored_source(md, &anchors, row.start_col..end).or(source.clone()),
+            };
+            wrapped.push(row.line);
+            row_starts.push((row.start_col, row.prefix_width));
+            wrapped_sources.push(row_source);
+        }
+    }
+    first_rows.push(wrapped.len());
 
-    // Post-processing for selection highlighting
-    if let Some(sel) = selection {
-        let (start_col, start_row) = sel.start;
-        let (end_col, end_row) = sel.end;
+    for heading in &mut headings {
+        heading.line = first_rows[heading.line];
+    }
 
-        // Normalize selection
-        let (s_col, s_row, e_col, e_row) =
-            if start_row < end_row || (start_row == end_row && start_col <= end_col) {
-                (start_col, start_row, end_col, end_row)
-            } else {
-                (end_col, end_row, start_col, start_row)
-            };
+    for footnote in &mut footnotes {
+        if let Some(lines) = &mut footnote.definition {
+            *lines = first_rows[lines.start]..first_rows[lines.end];
+        }
+    }
 
-        // Convert screen coordinates to relative line indices based on scroll_offset
-        // We know `lines` corresponds to the rendered text.
-        // We need to map viewport row to line index.
-        // Screen row `r` maps to `lines[r + scroll_offset]` ideally.
-
-        // Warning: `area_rect` gives us absolute screen coordinates, typically.
-        // But `markdown_to_ratatui` generates `Text` which is then rendered into a `Paragraph` with `scroll`.
-        // The `Paragraph` widget handles the scrolling.
-
-        // If we modify styles here, we are working on the WHOLE document.
-        // So we need to map the "visual selection" which is in screen coordinates relative to the viewport
-        // back to the local line indices.
-
-        // `s_row` is relative to the viewport top (e.g., 0 is the first visible line).
-        // So the actual line index is `s_row + scroll_offset`.
-
-        let start_line_idx = (s_row as usize).saturating_add(scroll_offset as usize);
-        let end_line_idx = (e_row as usize).saturating_add(scroll_offset as usize);
-
-        for (i, line) in lines.iter_mut().enumerate() {
-            if i >= start_line_idx && i <= end_line_idx {
-                // Determine column range for this line
-                // Determine column range for this line
-                // We need to rebuild spans with potentially modified styles.
-                // We need to rebuild spans with potentially modified styles.
-                // This is hard because a single line has multiple spans.
-                // For simplicity in this step, let's just create a new set of spans.
-
-                let mut new_spans = Vec::new();
-                let mut current_col = 0;
-
-                // Determine range of columns to highlight for this line
-                let (h_start, h_end) = if i == start_line_idx && i == end_line_idx {
-                    (s_col as usize, e_col as usize)
-                } else if i == start_line_idx {
-                    (s_col as usize, usize::MAX)
-                } else if i == end_line_idx {
-                    (0, e_col as usize)
-                } else {
-                    (0, usize::MAX)
-                };
+    let mut blocks: Vec<usize> = block_starts.into_iter().map(|i| first_rows[i]).collect();
+    blocks.dedup();
+
+    // Move links onto the wrapped row their text starts on, dropping those
+    // cut off the end of a table row.
+    links.retain_mut(|link| {
+        let rows = first_rows[link.line]..first_rows[link.line + 1];
+        let Some(row) = rows.rev().find(|&r| row_starts[r].0 <= link.start_col) else {
+            return true;
+        };
+        let (start, prefix_width) = row_starts[row];
+        let row_width = wrapped[row].width();
+        link.line = row;
+        link.start_col = link.start_col - start + prefix_width;
+        link.end_col = (link.end_col - start + prefix_width).min(row_width);
+        link.start_col < row_width
+    });
+
+    Rendered {
+        text: Text::from(wrapped),
+        source_map: SourceMap::new(wrapped_sources),
+        headings,
+        links,
+        blocks,
+        footnotes,
+        word_count,
+    }
+}
 
-                for span in &line.spans {
-                    let content = &span.content;
-                    let width = content.chars().count(); // approximate width
-                    let span_end = current_col + width;
+/// Source bytes of the inline content between columns `cols` of a line.
+/// Text shown exactly as written is narrowed to the bytes in those columns.
+fn anchored_source(md: &str, anchors: &[SourceAnchor], cols: Range<usize>) -> Option<Range<usize>> {
+    let mut covered: Option<Range<usize>> = None;
+    for (i, anchor) in anchors.iter().enumerate() {
+        let end = anchors.get(i + 1).map_or(usize::MAX, |next| next.col);
+        if anchor.col >= cols.end || end <= cols.start {
+            continue;
+        }
+        let mut source = anchor.source.clone();
+        if anchor.exact {
+            let text = &md[anchor.source.clone()];
+            if cols.start > anchor.col {
+                source.start += byte_at_col(text, cols.start - anchor.col);
+            }
+            if cols.end < end {
+                source.end = anchor.source.start + byte_at_col(text, cols.end - anchor.col);
+            }
+        }
+        covered = Some(match covered {
+            Some(c) => c.start.min(source.start)..c.end.max(source.end),
+            None => source,
+        });
+    }
+    covered
+}
 
-                    if span_end <= h_start || current_col >= h_end {
-                        // Span is completely outside selection
-                        new_spans.push(span.clone());
-                    } else {
-                        // Span overlaps selection
-                        // Split span into parts: before, selected, after
+/// Byte offset of the character at screen column `col` of `text`.
+fn byte_at_col(text: &str, col: usize) -> usize {
+    let mut width = 0;
+    for (i, c) in text.char_indices() {
+        if width >= col {
+            return i;
+        }
+        width += c.width().unwrap_or(0);
+    }
+    text.len()
+}
 
-                        let sel_start_in_span = h_start.saturating_sub(current_col);
-                        let sel_end_in_span = h_end.saturating_sub(current_col).min(width);
+/// The markdown source itself, one line per source line, highlighted with
+/// the markdown syntax.
+pub fn highlight_source(md: &str, theme_name: &str) -> Vec<Line<'static>> {
+    let mut highlighter = get_highlighter("markdown", theme_name);
+    md.lines()
+        .map(|line| {
+            let line = line.replace('\t', "    ");
+            match &mut highlighter {
+                Some(h) => Line::from(highlight_line(&line, h)),
+                None => Line::raw(line),
+            }
+        })
+        .collect()
+}
 
-                        if sel_start_in_span > 0 {
-                            let text = content.chars().take(sel_start_in_span).collect::<String>();
-                            new_spans.push(Span::styled(text, span.style));
-                        }
+/// Markdown extensions enabled for both the preview and HTML export.
+fn parser_options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_DEFINITION_LIST);
+    options.insert(Options::ENABLE_SUPERSCRIPT);
+    options.insert(Options::ENABLE_SUBSCRIPT);
+    options.insert(Options::ENABLE_WIKILINKS);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_GFM);
+    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    options.insert(Options::ENABLE_MATH);
+    options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+    options
+}
 
-                        if sel_end_in_span > sel_start_in_span {
-                            let text = content
-                                .chars()
-                                .skip(sel_start_in_span)
-                                .take(sel_end_in_span - sel_start_in_span)
-                                .collect::<String>();
-                            // Invert colors for selection
-                            let mut style = span.style;
-                            if let Some(bg) = style.bg {
-                                style = style.fg(bg);
-                            } else {
-                                style = style.fg(Color::Black);
-                            }
-                            style = style.bg(Color::White); // Default selection color
+/// Render markdown as a standalone HTML page (`:w file.html`).
+pub fn markdown_to_html(md: &str, title: &str) -> String {
+    let mut body = String::new();
+    html::push_html(&mut body, Parser::new_ext(md, parser_options()));
+    let title = title
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        title, body
+    )
+}
 
-                            new_spans.push(Span::styled(text, style));
-                        }
+/// Block-level tags, which nest inside each other but not inside inline ones.
+fn is_block(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::Heading { .. }
+            | Tag::BlockQuote(_)
+            | Tag::CodeBlock(_)
+            | Tag::HtmlBlock
+            | Tag::List(_)
+            | Tag::Item
+            | Tag::FootnoteDefinition(_)
+            | Tag::DefinitionList
+            | Tag::DefinitionListTitle
+            | Tag::DefinitionListDefinition
+            | Tag::Table(_)
+            | Tag::MetadataBlock(_)
+    )
+}
 
-                        if width > sel_end_in_span {
-                            let text = content.chars().skip(sel_end_in_span).collect::<String>();
-                            new_spans.push(Span::styled(text, span.style));
-                        }
-                    }
-                    current_col += width;
+fn is_block_end(tag: &TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::BlockQuote(_)
+            | TagEnd::CodeBlock
+            | TagEnd::HtmlBlock
+            | TagEnd::List(_)
+            | TagEnd::Item
+            | TagEnd::FootnoteDefinition
+            | TagEnd::DefinitionList
+            | TagEnd::DefinitionListTitle
+            | TagEnd::DefinitionListDefinition
+            | TagEnd::Table
+            | TagEnd::MetadataBlock(_)
+    )
+}
+
+/// Bullets of unordered lists, cycled by depth.
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
+
+/// A block whose lines are prefixed: a blockquote with its gutter, or a list
+/// item with its indentation.
+#[derive(Clone)]
+struct Container {
+    prefix: Span<'static>,
+    /// A list item's bullet or number, drawn instead of `prefix` on its
+    /// first line with text.
+    marker: Option<Span<'static>>,
+    /// A callout's bottom corner, drawn instead of `prefix` in front of the
+    /// bottom border of its box.
+    bottom: Option<Span<'static>>,
+}
+
+impl Container {
+    /// The ` ┃ ` gutter drawn in front of blockquote lines.
+    fn quote(style: Style) -> Self {
+        Container {
+            prefix: Span::styled(" ┃ ", style),
+            marker: None,
+            bottom: None,
+        }
+    }
+
+    /// The gutter of a callout's box, with the top corner drawn in front of
+    /// its title.
+    fn callout(style: Style) -> Self {
+        Container {
+            prefix: Span::styled(" ┃ ", style),
+            marker: Some(Span::styled(" ┏━", style)),
+            bottom: Some(Span::styled(" ┗━", style)),
+        }
+    }
+
+    fn item(marker: Span<'static>) -> Self {
+        Container {
+            prefix: Span::raw(" ".repeat(marker.width())),
+            marker: Some(marker),
+            bottom: None,
+        }
+    }
+}
+
+/// Put the prefixes of `containers` in front of `lines[range]`. Blank lines
+/// wait in `pending_blanks` with the depth they were pushed at (at most
+/// `outer_depth`), and only get quote gutters once more text follows.
+fn prefix_lines(
+    lines: &mut [Line<'static>],
+    range: Range<usize>,
+    containers: &mut [Container],
+    outer_depth: usize,
+    pending_blanks: &mut Vec<(usize, usize)>,
+    hanging_indents: &mut HashMap<usize, usize>,
+) {
+    for i in range {
+        if lines[i].width() == 0 {
+            pending_blanks.push((i, outer_depth));
+            continue;
+        }
+        for (blank, depth) in pending_blanks.drain(..) {
+            let outer = &containers[..depth.min(containers.len())];
+            // Indentation after the last gutter would only be trailing space.
+            let gutters = outer
+                .iter()
+                .rposition(|c| !c.prefix.content.trim().is_empty())
+                .map_or(0, |last| last + 1);
+            lines[blank].spans = outer[..gutters].iter().map(|c| c.prefix.clone()).collect();
+        }
+        if !containers.is_empty() {
+            let prefix: Vec<Span<'static>> = containers
+                .iter_mut()
+                .map(|c| c.marker.take().unwrap_or_else(|| c.prefix.clone()))
+                .collect();
+            *hanging_indents.entry(i).or_insert(0) += prefix.iter().map(Span::width).sum::<usize>();
+            lines[i].spans.splice(0..0, prefix);
+        }
+    }
+}
+
+/// Push a blank line between blocks, unless the last line already is one
+/// (still waiting in `pending_blanks` for its gutters).
+fn push_separator(lines: &mut Vec<Line<'static>>, pending_blanks: &[(usize, usize)]) {
+    if pending_blanks
+        .last()
+        .is_none_or(|&(blank, _)| blank + 1 != lines.len())
+    {
+        lines.push(Line::default());
+    }
+}
+
+/// A parser event and the source range it came from.
+type SourcedEvent<'a> = (MdEvent<'a>, Range<usize>);
+
+/// Move footnote definitions to the end of `events`, in the order of their
+/// numbers. Footnotes are numbered in order of first reference, including
+/// references from other definitions; unreferenced ones come last. Returns
+/// the events and the footnote labels in number order.
+fn move_footnotes(events: Vec<SourcedEvent>) -> (Vec<SourcedEvent>, Vec<String>) {
+    let mut body = Vec::with_capacity(events.len());
+    let mut definitions: Vec<(String, Vec<SourcedEvent>)> = Vec::new();
+    let mut in_definition = false;
+    for (event, range) in events {
+        if let MdEvent::Start(Tag::FootnoteDefinition(label)) = &event {
+            definitions.push((label.to_lowercase(), Vec::new()));
+            in_definition = true;
+        }
+        let ends = matches!(event, MdEvent::End(TagEnd::FootnoteDefinition));
+        match definitions.last_mut() {
+            Some((_, definition)) if in_definition => definition.push((event, range)),
+            _ => body.push((event, range)),
+        }
+        in_definition &= !ends;
+    }
+
+    let mut labels = Vec::new();
+    add_footnote_references(&body, &mut labels);
+    let mut i = 0;
+    while i < labels.len() {
+        if let Some((_, definition)) = definitions.iter().find(|(label, _)| *label == labels[i]) {
+            add_footnote_references(definition, &mut labels);
+        }
+        i += 1;
+    }
+    for (label, _) in &definitions {
+        if !labels.contains(label) {
+            labels.push(label.clone());
+        }
+    }
+
+    for label in &labels {
+        if let Some(i) = definitions.iter().position(|(l, _)| l == label) {
+            body.extend(definitions.remove(i).1);
+        }
+    }
+    (body, labels)
+}
+
+/// Add the labels of footnotes referenced in `events` to `labels`, if new.
+fn add_footnote_references(events: &[SourcedEvent], labels: &mut Vec<String>) {
+    for (event, _) in events {
+        if let MdEvent::FootnoteReference(label) = event {
+            let label = label.to_lowercase();
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+    }
+}
+
+/// Number (counted from 1) of the footnote `label` among `labels`.
+fn footnote_number(labels: &[String], label: &str) -> usize {
+    let label = label.to_lowercase();
+    labels.iter().position(|l| *l == label).map_or(0, |i| i + 1)
+}
+
+fn footnote_anchor(label: &str) -> String {
+    format!("fn-{}", label.to_lowercase())
+}
+
+/// Number of items in each list, keyed by where the list starts in the source.
+fn list_lengths(events: &[SourcedEvent]) -> HashMap<usize, u64> {
+    let mut lengths = HashMap::new();
+    let mut open: Vec<(usize, u64)> = Vec::new();
+    for (event, range) in events {
+        match event {
+            MdEvent::Start(Tag::List(_)) => open.push((range.start, 0)),
+            MdEvent::Start(Tag::Item) => {
+                if let Some((_, count)) = open.last_mut() {
+                    *count += 1;
+                }
+            }
+            MdEvent::End(TagEnd::List(_)) => {
+                if let Some((start, count)) = open.pop() {
+                    lengths.insert(start, count);
+                }
+            }
+            _ => {}
+        }
+    }
+    lengths
+}
+
+/// A GitHub alert or an Obsidian callout.
+struct Callout {
+    kind: BlockQuoteKind,
+    /// Title given after the `[!type]` marker, if not the kind's name.
+    title: Option<String>,
+    /// `Some(collapsed)` for a foldable callout (`[!type]-` or `[!type]+`).
+    folded: Option<bool>,
+}
+
+/// Parse an Obsidian callout header, `> [!type][+-] Title`, off the first
+/// line of a blockquote. Types GitHub doesn't know map to the closest alert.
+fn callout_header(quote: &str) -> Option<Callout> {
+    let first = quote.lines().next()?;
+    let rest = first.trim_start().strip_prefix('>')?.trim_start();
+    let (name, rest) = rest.strip_prefix("[!")?.split_once(']')?;
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    let (folded, rest) = match rest.strip_prefix('-') {
+        Some(rest) => (Some(true), rest),
+        None => match rest.strip_prefix('+') {
+            Some(rest) => (Some(false), rest),
+            None => (None, rest),
+        },
+    };
+    let kind = match name.to_lowercase().as_str() {
+        "tip" | "hint" | "success" | "check" | "done" | "question" | "help" | "faq" => {
+            BlockQuoteKind::Tip
+        }
+        "important" => BlockQuoteKind::Important,
+        "warning" | "attention" => BlockQuoteKind::Warning,
+        "caution" | "danger" | "error" | "failure" | "fail" | "missing" | "bug" => {
+            BlockQuoteKind::Caution
+        }
+        _ => BlockQuoteKind::Note,
+    };
+    let title = match rest.trim() {
+        "" => {
+            let mut chars = name.chars();
+            chars.next().map(|c| {
+                c.to_uppercase()
+                    .chain(chars.flat_map(|c| c.to_lowercase()))
+                    .collect()
+            })
+        }
+        title => Some(title.to_string()),
+    };
+    Some(Callout {
+        kind,
+        title,
+        folded,
+    })
+}
+
+/// The inline markdown of a one-line `text`, such as a callout title, in
+/// `style` with the emphasis of each part added.
+fn inline_spans(text: &str, style: Style, styles: &MarkdownStyles) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut modifiers = Modifier::empty();
+    for event in Parser::new_ext(text, parser_options()) {
+        match event {
+            MdEvent::Start(Tag::Strong) => modifiers.insert(Modifier::BOLD),
+            MdEvent::End(TagEnd::Strong) => modifiers.remove(Modifier::BOLD),
+            MdEvent::Start(Tag::Emphasis) => modifiers.insert(Modifier::ITALIC),
+            MdEvent::End(TagEnd::Emphasis) => modifiers.remove(Modifier::ITALIC),
+            MdEvent::Start(Tag::Strikethrough) => modifiers.insert(Modifier::CROSSED_OUT),
+            MdEvent::End(TagEnd::Strikethrough) => modifiers.remove(Modifier::CROSSED_OUT),
+            MdEvent::Text(text) | MdEvent::InlineMath(text) => {
+                spans.push(Span::styled(
+                    text.to_string(),
+                    style.add_modifier(modifiers),
+                ));
+            }
+            MdEvent::Code(code) => {
+                spans.push(Span::styled(format!(" {} ", code), styles.code));
+            }
+            MdEvent::SoftBreak | MdEvent::HardBreak => spans.push(Span::styled(" ", style)),
+            _ => {}
+        }
+    }
+    spans
+}
+
+/// Style, icon and name of an alert kind.
+fn alert_look(
+    kind: BlockQuoteKind,
+    styles: &MarkdownStyles,
+) -> (Style, &'static str, &'static str) {
+    match kind {
+        BlockQuoteKind::Note => (styles.note, "📝", "Note"),
+        BlockQuoteKind::Tip => (styles.tip, "💡", "Tip"),
+        BlockQuoteKind::Important => (styles.important, "❗", "Important"),
+        BlockQuoteKind::Warning => (styles.warning, "🔶", "Warning"),
+        BlockQuoteKind::Caution => (styles.caution, "🛑", "Caution"),
+    }
+}
+
+/// GitHub-style heading slug, with `-1`, `-2`, ... appended for duplicates.
+fn unique_anchor(text: &str, previous: &[HeadingEntry]) -> String {
+    let slug = heading_slug(text);
+    let mut anchor = slug.clone();
+    let mut n = 0;
+    while previous.iter().any(|h| h.anchor == anchor) {
+        n += 1;
+        anchor = format!("{}-{}", slug, n);
+    }
+    anchor
+}
+
+/// Lowercase, drop punctuation and turn spaces into dashes (`## Getting started!` → `getting-started`).
+pub fn heading_slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// One screen row produced by [`wrap_line`].
+struct WrappedRow {
+    line: Line<'static>,
+    /// Column of the unwrapped line that this row's content starts at.
+    start_col: usize,
+    /// Width of the hanging-indent prefix added in front of the content.
+    prefix_width: usize,
+}
+
+/// Word-wrap a line to `width` columns, breaking long words if needed.
+/// Continuation rows repeat the first `indent` columns as a hanging indent,
+/// keeping gutter characters (`┃`, `│`) and blanking bullets and markers.
+fn wrap_line(line: Line<'static>, width: usize, indent: usize) -> Vec<WrappedRow> {
+    if width == 0 || line.width() <= width {
+        return vec![WrappedRow {
+            line,
+            start_col: 0,
+            prefix_width: 0,
+        }];
+    }
+
+    let mut col = 0;
+    let cells: Vec<(char, Style, usize, usize)> = line
+        .spans
+        .iter()
+        .flat_map(|span| {
+            let style = line.style.patch(span.style);
+            span.content.chars().map(move |c| (c, style))
+        })
+        .map(|(c, style)| {
+            let w = c.width().unwrap_or(0);
+            col += w;
+            (c, style, w, col - w)
+        })
+        .collect();
+
+    // Leave at least a few columns for content on continuation rows.
+    let indent = if indent + 4 > width { 0 } else { indent };
+    let prefix_len = cells.iter().take_while(|c| c.3 < indent).count();
+    let continuation: Vec<(char, Style)> = cells[..prefix_len]
+        .iter()
+        .map(|&(c, style, _, _)| match c {
+            '┃' | '│' => (c, style),
+            '┏' => ('┃', style),
+            _ => (' ', Style::default()),
+        })
+        .collect();
+
+    let mut rows: Vec<(Vec<(char, Style)>, usize)> = Vec::new();
+    let mut row: Vec<(char, Style)> = cells[..prefix_len].iter().map(|c| (c.0, c.1)).collect();
+    let mut row_start = indent;
+    let mut row_width = indent;
+    let mut i = prefix_len;
+    while i < cells.len() {
+        // A word is a run of non-whitespace followed by its trailing whitespace.
+        let mut end = i;
+        while end < cells.len() && !cells[end].0.is_whitespace() {
+            end += 1;
+        }
+        let word_width: usize = cells[i..end].iter().map(|c| c.2).sum();
+        while end < cells.len() && cells[end].0.is_whitespace() {
+            end += 1;
+        }
+
+        if row_width > indent && row_width + word_width > width {
+            rows.push((std::mem::replace(&mut row, continuation.clone()), row_start));
+            row_width = indent;
+        }
+
+        for &(c, style, w, cell_col) in &cells[i..end] {
+            if row_width == indent && !rows.is_empty() && c.is_whitespace() {
+                // Don't start a continuation row with the whitespace we broke at.
+                continue;
+            }
+            if row_width + w > width {
+                if c.is_whitespace() {
+                    // Whitespace at the end of a row would run past its edge.
+                    continue;
+                }
+                rows.push((std::mem::replace(&mut row, continuation.clone()), row_start));
+                row_width = indent;
+            }
+            if row_width == indent {
+                row_start = cell_col;
+            }
+            row.push((c, style));
+            row_width += w;
+        }
+        i = end;
+    }
+    rows.push((row, row_start));
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, (cells, start_col))| {
+            let mut spans: Vec<Span<'static>> = Vec::new();
+            let mut text = String::new();
+            let mut current: Option<Style> = None;
+            for (c, style) in cells {
+                if current.is_some_and(|s| s != style) {
+                    spans.push(Span::styled(std::mem::take(&mut text), current.unwrap()));
                 }
-                *line = Line::from(new_spans);
+                current = Some(style);
+                text.push(c);
+            }
+            if let Some(style) = current {
+                spans.push(Span::styled(text, style));
+            }
+            WrappedRow {
+                line: Line::from(spans),
+                // The first row keeps its original prefix, so its columns line up as-is.
+                start_col: if i == 0 { 0 } else { start_col },
+                prefix_width: if i == 0 { 0 } else { indent },
+            }
+        })
+        .collect()
+}
+
+/// Column range `[start, end)` of `line_idx` covered by `sel`, if any.
+/// The selection is inclusive of the cell under both endpoints.
+fn selection_columns(sel: SelectionRange, line_idx: usize) -> Option<(usize, usize)> {
+    let (start_col, start_row) = sel.start;
+    let (end_col, end_row) = sel.end;
+
+    // Normalize selection
+    let (s_col, s_row, e_col, e_row) =
+        if start_row < end_row || (start_row == end_row && start_col <= end_col) {
+            (start_col, start_row, end_col, end_row)
+        } else {
+            (end_col, end_row, start_col, start_row)
+        };
+
+    let (s_row, e_row) = (s_row as usize, e_row as usize);
+    if line_idx < s_row || line_idx > e_row {
+        return None;
+    }
+    let start = if line_idx == s_row { s_col as usize } else { 0 };
+    let end = if line_idx == e_row {
+        e_col as usize + 1
+    } else {
+        usize::MAX
+    };
+    Some((start, end))
+}
+
+/// Highlight `sel` in a slice of rendered lines starting at document line `first_line`.
+pub fn highlight_selection(
+    lines: &mut [Line<'static>],
+    first_line: usize,
+    sel: SelectionRange,
+    color: Color,
+) {
+    for (i, line) in lines.iter_mut().enumerate() {
+        if let Some((h_start, h_end)) = selection_columns(sel, first_line + i) {
+            highlight_columns(line, h_start, h_end, |mut style| {
+                // Invert colors for selection
+                if let Some(bg) = style.bg {
+                    style = style.fg(bg);
+                } else {
+                    style = style.fg(Color::Black);
+                }
+                style.bg(color)
+            });
+        }
+    }
+}
+
+/// Restyle the screen columns `[h_start, h_end)` of a rendered line.
+pub fn highlight_columns(
+    line: &mut Line<'static>,
+    h_start: usize,
+    h_end: usize,
+    restyle: impl Fn(Style) -> Style,
+) {
+    let mut new_spans = Vec::new();
+    let mut current_col = 0;
+    for span in &line.spans {
+        let mut before = String::new();
+        let mut selected = String::new();
+        let mut after = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or(0);
+            // Wide characters count as selected if any of their cells are.
+            if current_col + w.max(1) <= h_start {
+                before.push(c);
+            } else if current_col >= h_end {
+                after.push(c);
+            } else {
+                selected.push(c);
+            }
+            current_col += w;
+        }
+
+        if !before.is_empty() {
+            new_spans.push(Span::styled(before, span.style));
+        }
+        if !selected.is_empty() {
+            new_spans.push(Span::styled(selected, restyle(span.style)));
+        }
+        if !after.is_empty() {
+            new_spans.push(Span::styled(after, span.style));
+        }
+    }
+    line.spans = new_spans;
+}
+
+/// Plain text covered by `sel` in the rendered lines, one line per row.
+pub fn selected_text(text: &Text<'_>, sel: SelectionRange) -> String {
+    let mut out: Vec<String> = Vec::new();
+    for (i, line) in text.lines.iter().enumerate() {
+        let Some((h_start, h_end)) = selection_columns(sel, i) else {
+            continue;
+        };
+        let mut row = String::new();
+        let mut current_col = 0;
+        for c in line.spans.iter().flat_map(|s| s.content.chars()) {
+            let w = c.width().unwrap_or(0);
+            if current_col + w.max(1) > h_start && current_col < h_end {
+                row.push(c);
             }
+            current_col += w;
         }
+        out.push(row.trim_end().to_string());
     }
+    out.join("\n")
+}
 
-    Text::from(lines)
+/// Markdown source lines behind the rendered lines covered by `sel`.
+pub fn selected_source<'a>(
+    md: &'a str,
+    source_map: &SourceMap,
+    sel: SelectionRange,
+) -> Option<&'a str> {
+    let first = sel.start.1.min(sel.end.1) as usize;
+    let last = sel.start.1.max(sel.end.1) as usize;
+    let ranges = (first..=last)
+        .filter(|&i| selection_columns(sel, i).is_some())
+        .filter_map(|i| source_map.source_of(i));
+    let (start, end) = ranges.fold(None, |acc: Option<(usize, usize)>, r| match acc {
+        Some((s, e)) => Some((s.min(r.start), e.max(r.end))),
+        None => Some((r.start, r.end)),
+    })?;
+
+    // Extend to whole source lines.
+    let start = md[..start].rfind('\n').map_or(0, |i| i + 1);
+    let end = md[end..].find('\n').map_or(md.len(), |i| end + i);
+    Some(md[start..end].trim_end_matches('\n'))
 }
 
+/// Lay out a table below a blank line. Rows wider than `width` are cut
+/// rather than wrapped, which would break up the columns. Returns the column
+/// each cell's content starts at, for the header and every row.
 fn render_table(
     lines: &mut Vec<Line<'static>>,
     headers: &[Vec<Span<'static>>],
     rows: &[Vec<Vec<Span<'static>>>],
     alignments: &[Alignment],
-) {
+    styles: &MarkdownStyles,
+    width: usize,
+) -> Vec<Vec<usize>> {
     if headers.is_empty() && rows.is_empty() {
-        return;
+        return Vec::new();
     }
 
     let col_count = headers
@@ -690,22 +1906,22 @@ fn render_table(
         .max(rows.iter().map(|r| r.len()).max().unwrap_or(0));
 
     if col_count == 0 {
-        return;
+        return Vec::new();
     }
 
-    // Calculate column widths based on content length (approximating display width with len for simplicity)
+    // Calculate column widths based on the display width of the content
     let mut col_widths: Vec<usize> = vec![5; col_count];
 
     // Headers
     for (i, cell) in headers.iter().enumerate() {
-        let cell_width: usize = cell.iter().map(|s| s.content.len()).sum();
+        let cell_width: usize = cell.iter().map(Span::width).sum();
         col_widths[i] = col_widths[i].max(cell_width);
     }
 
     // Rows
     for row in rows {
         for (i, cell) in row.iter().enumerate() {
-            let cell_width: usize = cell.iter().map(|s| s.content.len()).sum();
+            let cell_width: usize = cell.iter().map(Span::width).sum();
             if i < col_widths.len() {
                 col_widths[i] = col_widths[i].max(cell_width);
             }
@@ -717,78 +1933,110 @@ fn render_table(
         *width += 4;
     }
 
-    let border_color = Color::Rgb(135, 206, 250);
+    let border_style = styles.table_border;
     lines.push(Line::default());
+    // Column each cell's content starts at, for the header and every row.
+    let mut cell_starts: Vec<Vec<usize>> = Vec::new();
 
     // Always render headers if we have a column count (headers should always exist in markdown tables)
     if col_count > 0 {
         let mut header_line: Vec<Span<'static>> = vec![Span::raw("  ")];
-        let header_style = Style::default()
-            .fg(Color::Rgb(255, 182, 193))
-            .add_modifier(Modifier::BOLD);
+        let header_style = styles.table_header;
+        let mut starts = Vec::with_capacity(col_count);
 
-        for i in 0..col_count {
+        for (i, &width) in col_widths.iter().enumerate() {
             let cell = headers.get(i).cloned().unwrap_or_default();
-            let mut formatted = format_cell_spans(
-                &cell,
-                col_widths[i],
-                alignments.get(i).unwrap_or(&Alignment::None),
-            );
+            let (mut formatted, pad) =
+                format_cell_spans(&cell, width, alignments.get(i).unwrap_or(&Alignment::None));
+            starts.push(header_line.iter().map(Span::width).sum::<usize>() + pad);
             for span in &mut formatted {
                 span.style = span.style.patch(header_style);
             }
             header_line.extend(formatted);
 
             if i < col_count - 1 {
-                header_line.push(Span::styled(" │ ", Style::default().fg(border_color)));
+                header_line.push(Span::styled(" │ ", border_style));
             }
         }
-        lines.push(Line::from(header_line));
+        lines.push(truncate_line(Line::from(header_line), width));
+        cell_starts.push(starts);
 
         // Separator - must match header line indentation
         let mut sep: Vec<Span<'static>> = vec![Span::raw("  ")];
         for (i, &width) in col_widths.iter().enumerate() {
-            sep.push(Span::styled(
-                "─".repeat(width),
-                Style::default().fg(border_color),
-            ));
+            sep.push(Span::styled("─".repeat(width), border_style));
             if i < col_widths.len() - 1 {
-                sep.push(Span::styled("─┼─", Style::default().fg(border_color)));
+                sep.push(Span::styled("─┼─", border_style));
             }
         }
-        lines.push(Line::from(sep));
+        lines.push(truncate_line(Line::from(sep), width));
     }
 
     // Rows - must match header line indentation
     for row in rows {
         let mut row_line: Vec<Span<'static>> = vec![Span::raw("  ")];
-        for i in 0..col_count {
+        let mut starts = Vec::with_capacity(col_count);
+        for (i, &width) in col_widths.iter().enumerate() {
             let cell = row.get(i).cloned().unwrap_or_default();
-            let formatted = format_cell_spans(
-                &cell,
-                col_widths[i],
-                alignments.get(i).unwrap_or(&Alignment::None),
-            );
+            let (formatted, pad) =
+                format_cell_spans(&cell, width, alignments.get(i).unwrap_or(&Alignment::None));
+            starts.push(row_line.iter().map(Span::width).sum::<usize>() + pad);
             row_line.extend(formatted);
 
             if i < col_count - 1 {
-                row_line.push(Span::styled(" │ ", Style::default().fg(border_color)));
+                row_line.push(Span::styled(" │ ", border_style));
             }
         }
-        lines.push(Line::from(row_line));
+        lines.push(truncate_line(Line::from(row_line), width));
+        cell_starts.push(starts);
     }
 
     lines.push(Line::default());
+    cell_starts
 }
 
+/// Cut `line` to `width` columns, ending it with `…` if anything was cut.
+fn truncate_line(line: Line<'static>, width: usize) -> Line<'static> {
+    if line.width() <= width {
+        return line;
+    }
+    let mut left = width.saturating_sub(1);
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    for span in line.spans {
+        style = span.style;
+        let mut end = 0;
+        for (i, c) in span.content.char_indices() {
+            let w = c.width().unwrap_or(0);
+            if w > left {
+                break;
+            }
+            left -= w;
+            end = i + c.len_utf8();
+        }
+        let cut = end < span.content.len();
+        if end > 0 {
+            spans.push(Span::styled(span.content[..end].to_string(), span.style));
+        }
+        if cut {
+            break;
+        }
+    }
+    if width > 0 {
+        spans.push(Span::styled("…", style));
+    }
+    Line::from(spans)
+}
+
+/// Pad a cell to `width`, returning its spans and the padding in front.
 fn format_cell_spans(
     cell_spans: &[Span<'static>],
     width: usize,
     alignment: &Alignment,
-) -> Vec<Span<'static>> {
-    let current_width: usize = cell_spans.iter().map(|s| s.content.len()).sum();
+) -> (Vec<Span<'static>>, usize) {
+    let current_width: usize = cell_spans.iter().map(Span::width).sum();
     if current_width >= width {
-        return cell_spans.to_vec();
+        return (cell_spans.to_vec(), 0);
     }
 
     let padding = width - current_width;
@@ -796,12 +2044,12 @@ fn format_cell_spans(
         Alignment::Left | Alignment::None => {
             let mut v = cell_spans.to_vec();
             v.push(Span::raw(" ".repeat(padding)));
-            v
+            (v, 0)
         }
         Alignment::Right => {
             let mut v = vec![Span::raw(" ".repeat(padding))];
             v.extend_from_slice(cell_spans);
-            v
+            (v, padding)
         }
         Alignment::Center => {
             let left_pad = padding / 2;
@@ -809,7 +2057,7 @@ fn format_cell_spans(
             let mut v = vec![Span::raw(" ".repeat(left_pad))];
             v.extend_from_slice(cell_spans);
             v.push(Span::raw(" ".repeat(right_pad)));
-            v
+            (v, left_pad)
         }
     }
 }
@@ -848,3 +2096,225 @@ fn trim_spans(mut spans: Vec<Span<'static>>) -> Vec<Span<'static>> {
 
     spans
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// Render `md` into a preview `width` columns wide (borders included).
+    fn render(md: &str, width: u16) -> Rendered {
+        let config = Config::default();
+        let area = ratatui::layout::Rect::new(0, 0, width, 20);
+        markdown_to_ratatui(md, &config.theme, &config.markdown, area, false)
+    }
+
+    #[test]
+    fn paragraphs_map_to_their_source_lines() {
+        let md = "First paragraph.\n\nSecond\nparagraph.\n";
+        let map = render(md, 40).source_map;
+
+        assert_eq!(map.source_of(0), Some(0..16));
+        assert_eq!(map.source_of(1), None);
+        assert_eq!(map.source_of(2), Some(18..35));
+        assert_eq!(map.line_of_source_line(md, 1), Some(0));
+        // A blank source line goes to the next block, whose two source
+        // lines share one rendered line.
+        assert_eq!(map.line_of_source_line(md, 2), Some(2));
+        assert_eq!(map.line_of_source_line(md, 4), Some(2));
+        assert_eq!(map.source_line_at(md, 0), Some(1));
+        assert_eq!(map.source_line_at(md, 1), Some(3));
+    }
+
+    #[test]
+    fn code_block_lines_map_one_to_one() {
+        let md = "Intro\n\n```rust\nlet a = 1;\nlet b = 2;\n```\n";
+        let map = render(md, 40).source_map;
+
+        // Blank line, then the top border, the two code lines and the bottom border.
+        assert_eq!(map.source_of(4), Some(15..25));
+        assert_eq!(map.source_of(5), Some(26..36));
+        assert_eq!(map.line_of_source_line(md, 3), Some(3));
+        assert_eq!(map.line_of_source_line(md, 4), Some(4));
+        assert_eq!(map.line_of_source_line(md, 5), Some(5));
+        assert_eq!(map.source_line_at(md, 4), Some(4));
+        assert_eq!(map.source_line_at(md, 5), Some(5));
+        assert_eq!(map.line_of(md.find("b = 2").unwrap()), Some(5));
+    }
+
+    #[test]
+    fn wrapped_rows_show_part_of_their_line() {
+        let md = "one two three four five six seven eight nine ten\n";
+        let rendered = render(md, 22);
+        let map = &rendered.source_map;
+
+        assert_eq!(rendered.text.lines.len(), 4);
+        assert_eq!(map.source_of(0), Some(0..19));
+        assert_eq!(map.source_of(1), Some(19..40));
+        assert_eq!(map.source_of(2), Some(40..48));
+        assert_eq!(map.line_of(md.find("six").unwrap()), Some(1));
+        assert_eq!(map.line_of_source_line(md, 1), Some(0));
+        assert_eq!(map.source_line_at(md, 2), Some(1));
+    }
+
+    #[test]
+    fn table_rows_map_to_their_source_lines() {
+        let md = "| a | b |\n|---|---|\n| 1 | 2 |\n| 3 | 4 |\n";
+        let map = render(md, 40).source_map;
+
+        // A blank line, the header and separator, then one line per row.
+        assert_eq!(map.source_of(0), None);
+        assert_eq!(map.source_of(1), Some(0..10));
+        assert_eq!(map.source_of(2), Some(0..10));
+        assert_eq!(map.source_of(3), Some(20..30));
+        assert_eq!(map.source_of(4), Some(30..40));
+        assert_eq!(map.line_of_source_line(md, 1), Some(1));
+        assert_eq!(map.line_of_source_line(md, 4), Some(4));
+        assert_eq!(map.source_line_at(md, 3), Some(3));
+    }
+
+    #[test]
+    fn past_the_end_maps_to_nothing() {
+        let md = "Only line.\n";
+        let rendered = render(md, 40);
+        let map = &rendered.source_map;
+
+        assert_eq!(map.line_of_source_line(md, 3), None);
+        assert_eq!(map.line_of(md.len()), None);
+        assert_eq!(map.source_line_at(md, rendered.text.lines.len()), None);
+        assert_eq!(map.source_of(rendered.text.lines.len()), None);
+    }
+
+    /// The rendered rows of `md` as plain text.
+    fn rows(md: &str, width: u16) -> Vec<String> {
+        render(md, width)
+            .text
+            .lines
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn rules_fit_inside_quotes_and_items() {
+        let quoted = rows("> ---\n", 30);
+        assert_eq!(quoted[1], format!(" ┃ {}", "─".repeat(25)));
+        let item = rows("- a\n\n  ---\n", 30);
+        assert_eq!(item[3], format!("   {}", "─".repeat(25)));
+    }
+
+    #[test]
+    fn adjacent_blocks_are_separated_by_one_blank_line() {
+        let md = "> [!note]\n> one\n\n> [!tip]\n> two\n\n- x\n\n> q\n";
+        assert_eq!(
+            rows(md, 30)[1..],
+            [
+                " ┏━ 📝 Note ━━━━━━━━━━━━━━━━",
+                " ┃ one",
+                " ┗━━━━━━━━━━━━━━━━━━━━━━━━━━",
+                "",
+                " ┏━ 💡 Tip ━━━━━━━━━━━━━━━━━",
+                " ┃ two",
+                " ┗━━━━━━━━━━━━━━━━━━━━━━━━━━",
+                "",
+                " • x",
+                "",
+                " ┃ q",
+                "",
+            ]
+        );
+        // Nested quotes keep the outer gutter on the blank line between them.
+        assert_eq!(
+            rows("> a\n>\n> > b\n", 30)[1..4],
+            [" ┃ a", " ┃ ", " ┃  ┃ b"]
+        );
+    }
+
+    #[test]
+    fn wrapped_rows_fit_the_width() {
+        let md = "a  b  c  d  e  f  g  h  i  j  k  l  m\n";
+        assert_eq!(
+            rows(md, 30)[..2],
+            ["a  b  c  d  e  f  g  h  i  j", "k  l  m"]
+        );
+    }
+
+    #[test]
+    fn tables_are_cut_to_the_width() {
+        let md =
+            "> | Name | Description |\n> |---|---|\n> | 二つ | [a link](http://x.y) far away |\n";
+        let rendered = render(md, 30);
+        let rows: Vec<String> = rendered
+            .text
+            .lines
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        assert_eq!(
+            rows[2..5],
+            [
+                " ┃   Name      │ Descriptio…",
+                " ┃   ──────────┼───────────…",
+                " ┃   二つ      │ a link (🔗…",
+            ]
+        );
+        assert!(rendered.text.lines.iter().all(|line| line.width() <= 28));
+        assert_eq!(rendered.links[0].line, 4);
+        assert_eq!(rendered.links[0].end_col, 28);
+    }
+
+    #[test]
+    fn callouts_are_boxed_with_their_title() {
+        let rendered = render("> [!tip]- Custom *title*\n> hidden\n", 30);
+        let title = &rendered.text.lines[1];
+        assert_eq!(
+            title
+                .spans
+                .iter()
+                .map(|s| s.content.as_ref())
+                .collect::<String>(),
+            " ┏━ 💡 Custom title ▸ ━━━━━━"
+        );
+        let emphasis = title.spans.iter().find(|s| s.content == "title").unwrap();
+        assert!(emphasis.style.add_modifier.contains(Modifier::ITALIC));
+        assert_eq!(rendered.text.lines[2].spans[1].content, "━".repeat(25));
+
+        let md = "- item\n\n  > [!note]\n  > body\n";
+        assert_eq!(
+            rows(md, 30)[3..6],
+            [
+                "    ┏━ 📝 Note ━━━━━━━━━━━━━",
+                "    ┃ body",
+                "    ┗━━━━━━━━━━━━━━━━━━━━━━━",
+            ]
+        );
+    }
+
+    #[test]
+    fn callout_headers() {
+        let callout = callout_header("> [!warning] Check this\n> body").unwrap();
+        assert_eq!(callout.kind, BlockQuoteKind::Warning);
+        assert_eq!(callout.title.as_deref(), Some("Check this"));
+        assert_eq!(callout.folded, None);
+
+        // Without a title the type's name is shown.
+        let callout = callout_header(">[!faq]-\n> hidden").unwrap();
+        assert_eq!(callout.kind, BlockQuoteKind::Tip);
+        assert_eq!(callout.title.as_deref(), Some("Faq"));
+        assert_eq!(callout.folded, Some(true));
+
+        // Unknown types look like a note.
+        let callout = callout_header("> [!custom]+ Open").unwrap();
+        assert_eq!(callout.kind, BlockQuoteKind::Note);
+        assert_eq!(callout.title.as_deref(), Some("Open"));
+        assert_eq!(callout.folded, Some(false));
+    }
+
+    #[test]
+    fn plain_quotes_are_not_callouts() {
+        assert!(callout_header("> Just a quote").is_none());
+        assert!(callout_header("> [!] Empty type").is_none());
+        assert!(callout_header("> [!two words] Title").is_none());
+        assert!(callout_header("> [link](url)").is_none());
+    }
+}
@@ -0,0 +1,108 @@
+use ratatui::text::Text;
+use regex::{Regex, RegexBuilder};
+use unicode_width::UnicodeWidthStr;
+
+/// A match in the rendered document, in screen columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub line: usize,
+    pub start_col: usize,
+    pub end_col: usize,
+}
+
+/// An active in-document search and its matches in the rendered preview.
+pub struct Search {
+    pub pattern: String,
+    /// `true` for `/` searches, `false` for `?` searches.
+    pub forward: bool,
+    regex: Regex,
+    pub matches: Vec<SearchMatch>,
+    pub current: Option<usize>,
+}
+
+impl Search {
+    pub fn new(pattern: &str, forward: bool) -> Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: pattern.to_string(),
+            forward,
+            regex: build_regex(pattern)?,
+            matches: Vec::new(),
+            current: None,
+        })
+    }
+
+    /// Recompute matches against freshly rendered text, keeping the current
+    /// match index where possible.
+    pub fn refresh(&mut self, text: &Text<'_>) {
+        self.matches.clear();
+        for (line_idx, line) in text.lines.iter().enumerate() {
+            let content: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+            for m in self.regex.find_iter(&content) {
+                if m.is_empty() {
+                    continue;
+                }
+                let start_col = content[..m.start()].width();
+                self.matches.push(SearchMatch {
+                    line: line_idx,
+                    start_col,
+                    end_col: start_col + m.as_str().width(),
+                });
+            }
+        }
+
+        self.current = match self.current {
+            _ if self.matches.is_empty() => None,
+            Some(i) => Some(i.min(self.matches.len() - 1)),
+            None => None,
+        };
+    }
+
+    /// Select the first match at or after `line` (or at or before it for
+    /// backward searches), wrapping around the document.
+    pub fn select_from(&mut self, line: usize) -> Option<SearchMatch> {
+        if self.matches.is_empty() {
+            self.current = None;
+            return None;
+        }
+        let index = if self.forward {
+            self.matches
+                .iter()
+                .position(|m| m.line >= line)
+                .unwrap_or(0)
+        } else {
+            self.matches
+                .iter()
+                .rposition(|m| m.line <= line)
+                .unwrap_or(self.matches.len() - 1)
+        };
+        self.current = Some(index);
+        self.matches.get(index).copied()
+    }
+
+    /// Move to the next match in the search direction (`n`), or the
+    /// opposite direction when `reverse` is set (`N`).
+    pub fn step(&mut self, reverse: bool) -> Option<SearchMatch> {
+        let len = self.matches.len();
+        if len == 0 {
+            return None;
+        }
+        let forward = self.forward != reverse;
+        let index = match self.current {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None if forward => 0,
+            None => len - 1,
+        };
+        self.current = Some(index);
+        self.matches.get(index).copied()
+    }
+}
+
+/// Compile `pattern` as a regex. Matching is case-insensitive unless the
+/// pattern contains an uppercase letter (smart-case).
+pub fn build_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let case_insensitive = !pattern.chars().any(char::is_uppercase);
+    RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+}
@@ -56,14 +56,14 @@ pub fn highlight_line(line: &str, highlighter: &mut HighlightLines) -> Vec<Span<
         .collect();
 
     // Remove the trailing newline character from the last span if present
-    if let Some(last_span) = spans.last_mut() {
-        if last_span.content.ends_with('\n') {
-            let new_content = last_span.content.trim_end_matches('\n').to_string();
-            if new_content.is_empty() {
-                spans.pop();
-            } else {
-                *last_span = Span::styled(new_content, last_span.style);
-            }
+    if let Some(last_span) = spans.last_mut()
+        && last_span.content.ends_with('\n')
+    {
+        let new_content = last_span.content.trim_end_matches('\n').to_string();
+        if new_content.is_empty() {
+            spans.pop();
+        } else {
+            *last_span = Span::styled(new_content, last_span.style);
         }
     }
 
@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// A single file or directory known to the tree.
+pub struct TreeNode {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+    pub parent: Option<usize>,
+    /// Child node indices; `None` until the directory is expanded for the first time.
+    pub children: Option<Vec<usize>>,
+}
+
+/// A row of the tree as it is currently displayed.
+pub struct VisibleNode {
+    pub node: usize,
+    /// One entry per ancestor level: whether a vertical guide line passes through this row.
+    pub guides: Vec<bool>,
+    /// Whether this node is the last child of its parent.
+    pub is_last: bool,
+}
+
+/// Lazily loaded directory tree rooted at `root`.
+pub struct FileTree {
+    pub root: PathBuf,
+    nodes: Vec<TreeNode>,
+    top_level: Vec<usize>,
+    visible: Vec<VisibleNode>,
+}
+
+impl FileTree {
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let mut tree = Self {
+            root: root.to_path_buf(),
+            nodes: Vec::new(),
+            top_level: Vec::new(),
+            visible: Vec::new(),
+        };
+        tree.top_level = tree.load_children(root, 0, None)?;
+        tree.rebuild_visible();
+        Ok(tree)
+    }
+
+    pub fn visible(&self) -> &[VisibleNode] {
+        &self.visible
+    }
+
+    pub fn len(&self) -> usize {
+        self.visible.len()
+    }
+
+    pub fn node(&self, index: usize) -> &TreeNode {
+        &self.nodes[index]
+    }
+
+    /// Node displayed at the given visible row.
+    pub fn node_at(&self, row: usize) -> Option<&TreeNode> {
+        self.visible.get(row).map(|v| &self.nodes[v.node])
+    }
+
+    /// Visible row of the node with the given path, if it is currently shown.
+    pub fn row_of(&self, path: &Path) -> Option<usize> {
+        self.visible
+            .iter()
+            .position(|v| normalize_path(&self.nodes[v.node].path) == normalize_path(path))
+    }
+
+    /// Expand the directories leading to `path` and return its row.
+    pub fn reveal(&mut self, path: &Path) -> Option<usize> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let mut dir = self.root.clone();
+        if let Some(parent) = relative.parent() {
+            for component in parent.components() {
+                dir.push(component);
+                let row = self.row_of(&dir)?;
+                self.expand(row);
+            }
+        }
+        self.row_of(path)
+    }
+
+    /// Expand the directory at `row`, loading its children on first use.
+    /// Returns `false` if the row is not a collapsed directory.
+    pub fn expand(&mut self, row: usize) -> bool {
+        let Some(index) = self.visible.get(row).map(|v| v.node) else {
+            return false;
+        };
+        if !self.nodes[index].is_dir || self.nodes[index].expanded {
+            return false;
+        }
+
+        if self.nodes[index].children.is_none() {
+            let path = self.nodes[index].path.clone();
+            let depth = self.nodes[index].depth + 1;
+            // Unreadable directories simply show up empty.
+            let children = self
+                .load_children(&path, depth, Some(index))
+                .unwrap_or_default();
+            self.nodes[index].children = Some(children);
+        }
+
+        self.nodes[index].expanded = true;
+        self.rebuild_visible();
+        true
+    }
+
+    /// Collapse the directory at `row`. If the row is a file or an already
+    /// collapsed directory, returns the row of its parent instead.
+    pub fn collapse(&mut self, row: usize) -> Option<usize> {
+        let index = self.visible.get(row)?.node;
+        if self.nodes[index].is_dir && self.nodes[index].expanded {
+            self.nodes[index].expanded = false;
+            self.rebuild_visible();
+            return Some(row);
+        }
+
+        let parent = self.nodes[index].parent?;
+        self.visible.iter().position(|v| v.node == parent)
+    }
+
+    pub fn toggle(&mut self, row: usize) {
+        let expanded = self.node_at(row).is_some_and(|n| n.expanded);
+        if expanded {
+            self.collapse(row);
+        } else {
+            self.expand(row);
+        }
+    }
+
+    /// The root plus every directory whose children have been loaded.
+    pub fn loaded_dirs(&self) -> Vec<PathBuf> {
+        std::iter::once(self.root.clone())
+            .chain(
+                self.nodes
+                    .iter()
+                    .filter(|n| n.children.is_some())
+                    .map(|n| n.path.clone()),
+            )
+            .collect()
+    }
+
+    /// Re-read the tree from disk, keeping visible directories expanded.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        let expanded: HashSet<PathBuf> = self
+            .nodes
+            .iter()
+            .filter(|n| n.expanded)
+            .map(|n| n.path.clone())
+            .collect();
+
+        let mut fresh = FileTree::new(&self.root)?;
+        // Expanding a row only adds rows after it, so one pass re-expands nested dirs too.
+        let mut row = 0;
+        while row < fresh.len() {
+            if fresh
+                .node_at(row)
+                .is_some_and(|n| n.is_dir && expanded.contains(&n.path))
+            {
+                fresh.expand(row);
+            }
+            row += 1;
+        }
+
+        *self = fresh;
+        Ok(())
+    }
+
+    fn load_children(
+        &mut self,
+        dir: &Path,
+        depth: usize,
+        parent: Option<usize>,
+    ) -> io::Result<Vec<usize>> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+
+        // Sort entries by name (case-insensitive)
+        entries.sort_by_key(|p| file_name(p).to_lowercase());
+
+        let mut children = Vec::with_capacity(entries.len());
+        for path in entries {
+            children.push(self.nodes.len());
+            self.nodes.push(TreeNode {
+                name: file_name(&path),
+                is_dir: path.is_dir(),
+                path,
+                depth,
+                expanded: false,
+                parent,
+                children: None,
+            });
+        }
+        Ok(children)
+    }
+
+    fn rebuild_visible(&mut self) {
+        let mut visible = Vec::new();
+        self.collect_visible(&self.top_level, &[], &mut visible);
+        self.visible = visible;
+    }
+
+    fn collect_visible(&self, children: &[usize], guides: &[bool], out: &mut Vec<VisibleNode>) {
+        for (i, &index) in children.iter().enumerate() {
+            let is_last = i + 1 == children.len();
+            out.push(VisibleNode {
+                node: index,
+                guides: guides.to_vec(),
+                is_last,
+            });
+
+            let node = &self.nodes[index];
+            if let (true, Some(grandchildren)) = (node.expanded, &node.children) {
+                // Top-level entries are drawn without connectors, so their
+                // children do not need a guide for them.
+                let mut child_guides = guides.to_vec();
+                if node.depth > 0 {
+                    child_guides.push(!is_last);
+                }
+                self.collect_visible(grandchildren, &child_guides, out);
+            }
+        }
+    }
+}
+
+/// `path` without `.` components and with `dir/..` pairs removed, so that
+/// `./docs/../README.md` and `README.md` compare equal.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    normalized
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
@@ -1,18 +1,32 @@
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
 };
+use unicode_width::UnicodeWidthStr;
 
-use crate::app::{App, FocusedPane};
-use crate::renderer::markdown_to_ratatui;
+use crate::app::{App, FocusedPane, SourceView};
+use crate::keymap::format_keys;
+use crate::renderer::{highlight_columns, highlight_selection};
 
 pub fn render_ui(f: &mut Frame, app: &mut App) {
-    let area = f.area();
+    // Reserve the bottom row for the search prompt / status messages, and
+    // the top row for the tab bar while several documents are open.
+    let tab_bar_height = if app.buffers.len() > 1 { 1 } else { 0 };
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(tab_bar_height),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+    let area = rows[1];
 
     let chunks = if app.show_tree {
-        let tree = app.tree_width_percentage.min(80).max(10);
+        let tree = app.tree_width_percentage.clamp(10, 80);
         let preview = 100u16.saturating_sub(tree);
         Layout::default()
             .direction(Direction::Horizontal)
@@ -36,72 +50,94 @@ pub fn render_ui(f: &mut Frame, app: &mut App) {
         app.last_tree_width_px = area.width;
     }
 
-    let preview_area = chunks[if app.show_tree { 1 } else { 0 }];
-    let rendered = markdown_to_ratatui(
-        &app.markdown,
-        &app.current_theme,
-        app.selection,
-        app.scroll_offset,
-        preview_area,
-    );
-    let line_count = rendered.lines.len() as u16;
-
-    let viewport_height = if app.show_tree {
-        chunks[1].height.saturating_sub(2)
-    } else {
-        chunks[0].height.saturating_sub(2)
+    // The source view takes half the pane or all of it. In the latter case
+    // the preview is still laid out, unseen, so motions keep working on it.
+    let pane = chunks[if app.show_tree { 1 } else { 0 }];
+    let (preview_area, source_area) = match app.source_view {
+        SourceView::Hidden => (pane, None),
+        SourceView::Split => {
+            let halves = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(pane);
+            (halves[0], Some(halves[1]))
+        }
+        SourceView::Only => (pane, Some(pane)),
     };
+    app.last_preview_area = preview_area;
+    app.ensure_rendered();
 
+    let viewport_height = preview_area.height.saturating_sub(2);
+    let line_count = app.rendered().map_or(0, |r| r.text.lines.len()) as u16;
     app.update_max_scroll(line_count, viewport_height);
 
+    let visible_lines = visible_preview_lines(app);
+    let palette = app.config.colors.clone();
+
     let mut preview_block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Rgb(255, 182, 193)))
-        .title(" 📄 Markdown Preview ")
+        .border_style(Style::default().fg(palette.preview_border))
+        .title(preview_title(app))
         .title_style(
             Style::default()
-                .fg(Color::Rgb(255, 182, 193))
+                .fg(palette.preview_border)
                 .add_modifier(Modifier::BOLD),
         );
 
     if app.focused_pane == FocusedPane::Preview {
         preview_block = preview_block.border_style(
             Style::default()
-                .fg(Color::Rgb(255, 105, 180))
+                .fg(palette.accent)
                 .add_modifier(Modifier::BOLD),
         );
     }
 
-    let preview = Paragraph::new(rendered)
-        .block(preview_block)
-        .scroll((app.scroll_offset, 0))
-        .wrap(ratatui::widgets::Wrap { trim: false });
+    // The renderer already wrapped the text to the preview width.
+    let preview = Paragraph::new(visible_lines).block(preview_block);
 
     if app.show_tree {
+        let guide_style = Style::default().fg(palette.tree_guide);
         let items: Vec<ListItem> = app
-            .files
+            .tree
+            .visible()
             .iter()
-            .map(|p| {
-                let name = p.file_name().unwrap_or_default().to_string_lossy();
-                let icon = if p.is_dir() { "📁 " } else { "📄 " };
-                ListItem::new(format!("{}{}", icon, name))
+            .map(|row| {
+                let node = app.tree.node(row.node);
+                let mut spans: Vec<Span> = row
+                    .guides
+                    .iter()
+                    .map(|&continues| {
+                        Span::styled(if continues { "│  " } else { "   " }, guide_style)
+                    })
+                    .collect();
+                if node.depth > 0 {
+                    let connector = if row.is_last { "└─ " } else { "├─ " };
+                    spans.push(Span::styled(connector, guide_style));
+                }
+                let icon = match (node.is_dir, node.expanded) {
+                    (true, true) => "📂 ",
+                    (true, false) => "📁 ",
+                    _ => "📄 ",
+                };
+                spans.push(Span::raw(format!("{}{}", icon, node.name)));
+                ListItem::new(Line::from(spans))
             })
             .collect();
 
         let mut tree_block = Block::default()
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Rgb(135, 206, 250)))
+            .border_style(Style::default().fg(palette.tree_border))
             .title(" 📂 Files ")
             .title_style(
                 Style::default()
-                    .fg(Color::Rgb(135, 206, 250))
+                    .fg(palette.tree_border)
                     .add_modifier(Modifier::BOLD),
             );
 
         if app.focused_pane == FocusedPane::FileTree {
             tree_block = tree_block.border_style(
                 Style::default()
-                    .fg(Color::Rgb(255, 105, 180))
+                    .fg(palette.accent)
                     .add_modifier(Modifier::BOLD),
             );
         }
@@ -110,18 +146,45 @@ pub fn render_ui(f: &mut Frame, app: &mut App) {
             .block(tree_block)
             .highlight_style(
                 Style::default()
-                    .fg(Color::Rgb(255, 105, 180))
+                    .fg(palette.accent)
                     .add_modifier(Modifier::BOLD),
             )
             .highlight_symbol("▶ ");
 
-        let mut state = ListState::default();
-        state.select(Some(app.selected));
+        app.tree_state.select(Some(app.selected));
 
-        f.render_stateful_widget(list, chunks[0], &mut state);
-        f.render_widget(preview, chunks[1]);
-    } else {
-        f.render_widget(preview, chunks[0]);
+        f.render_stateful_widget(list, chunks[0], &mut app.tree_state);
+    }
+    match source_area {
+        Some(source_area) => {
+            if source_area != preview_area {
+                f.render_widget(preview, preview_area);
+            }
+            render_source(f, app, source_area);
+        }
+        None => f.render_widget(preview, preview_area),
+    }
+
+    if tab_bar_height > 0 {
+        render_tab_bar(f, app, rows[0]);
+    }
+    render_status_line(f, app, rows[2]);
+    render_completions(f, app, rows[2]);
+
+    if app.show_outline {
+        render_outline(f, app);
+    }
+
+    if app.show_buffer_list {
+        render_buffer_list(f, app);
+    }
+
+    if app.finder.is_some() {
+        render_finder(f, app);
+    }
+
+    if app.project_search.is_some() {
+        render_project_search(f, app);
     }
 
     // Render theme selection popup
@@ -129,7 +192,7 @@ pub fn render_ui(f: &mut Frame, app: &mut App) {
         let block = Block::default()
             .borders(Borders::ALL)
             .title(" Select Theme ")
-            .border_style(Style::default().fg(Color::Yellow));
+            .border_style(Style::default().fg(palette.popup_border));
 
         let area = centered_rect(60, 40, f.area());
         f.render_widget(Clear, area); // Clear background
@@ -140,7 +203,7 @@ pub fn render_ui(f: &mut Frame, app: &mut App) {
             .map(|t| {
                 let style = if t == &app.current_theme {
                     Style::default()
-                        .fg(Color::Green)
+                        .fg(palette.popup_current)
                         .add_modifier(Modifier::BOLD)
                 } else {
                     Style::default()
@@ -151,7 +214,7 @@ pub fn render_ui(f: &mut Frame, app: &mut App) {
 
         let list = List::new(items).block(block).highlight_style(
             Style::default()
-                .bg(Color::DarkGray)
+                .bg(palette.popup_selected)
                 .add_modifier(Modifier::BOLD),
         );
 
@@ -159,6 +222,588 @@ pub fn render_ui(f: &mut Frame, app: &mut App) {
     }
 }
 
+/// Clone the rows of the cached render that fit in the preview and overlay
+/// the cursor line, selection, search matches and focused link on them.
+fn visible_preview_lines(app: &App) -> Vec<Line<'static>> {
+    let Some(rendered) = app.rendered() else {
+        return Vec::new();
+    };
+    let first = (app.scroll_offset as usize).min(rendered.text.lines.len());
+    let last = (first + app.viewport_height as usize).min(rendered.text.lines.len());
+    let mut lines = rendered.text.lines[first..last].to_vec();
+
+    if app.focused_pane == FocusedPane::Preview
+        && let Some(line) = app
+            .cursor_line
+            .checked_sub(first)
+            .and_then(|i| lines.get_mut(i))
+    {
+        // Pad to the full width so the highlight spans the whole row.
+        let inner_width = app.last_preview_area.width.saturating_sub(2) as usize;
+        line.spans.push(Span::raw(
+            " ".repeat(inner_width.saturating_sub(line.width())),
+        ));
+        line.style = line.style.bg(app.config.colors.cursor_line);
+    }
+
+    if let Some(sel) = app.selection {
+        highlight_selection(&mut lines, first, sel, app.config.colors.selection);
+    }
+
+    if let Some(search) = &app.search {
+        // Matches are in document order, so only the visible run is walked.
+        let start = search.matches.partition_point(|m| m.line < first);
+        for (i, m) in search.matches.iter().enumerate().skip(start) {
+            if m.line >= last {
+                break;
+            }
+            let bg = if search.current == Some(i) {
+                app.config.colors.search_current
+            } else {
+                app.config.colors.search_match
+            };
+            highlight_columns(&mut lines[m.line - first], m.start_col, m.end_col, |s| {
+                s.fg(Color::Black).bg(bg)
+            });
+        }
+    }
+
+    if let Some(link) = app.focused_link.and_then(|i| app.links.get(i))
+        && (first..last).contains(&link.line)
+    {
+        highlight_columns(
+            &mut lines[link.line - first],
+            link.start_col,
+            link.end_col,
+            |s| s.add_modifier(Modifier::REVERSED),
+        );
+    }
+
+    lines
+}
+
+/// Preview title, including the section at the top of the preview.
+fn preview_title(app: &App) -> String {
+    match app.current_heading().map(|i| &app.headings[i]) {
+        Some(heading) => format!(" 📄 Markdown Preview — § {} ", heading.text),
+        None => " 📄 Markdown Preview ".to_string(),
+    }
+}
+
+/// The raw markdown with line numbers, scrolled so the source of the cursor
+/// line sits level with it in the preview.
+fn render_source(f: &mut Frame, app: &mut App, area: Rect) {
+    let palette = app.config.colors.clone();
+    let (cursor, top) = app.source_view_position();
+    let show_cursor = app.focused_pane == FocusedPane::Preview;
+    let focused = show_cursor && app.source_view == SourceView::Only;
+    let height = area.height.saturating_sub(2) as usize;
+    let inner_width = area.width.saturating_sub(2) as usize;
+
+    let lines = app.source_lines();
+    let number_width = lines.len().max(1).to_string().len();
+    let visible: Vec<Line> = lines
+        .iter()
+        .enumerate()
+        .skip(top)
+        .take(height)
+        .map(|(i, line)| {
+            let number = Span::styled(
+                format!("{:>width$} ", i + 1, width = number_width),
+                Style::default().fg(palette.tree_guide),
+            );
+            let mut line = Line::from(
+                std::iter::once(number)
+                    .chain(line.spans.iter().cloned())
+                    .collect::<Vec<_>>(),
+            );
+            if show_cursor && cursor.contains(&i) {
+                line.spans.push(Span::raw(
+                    " ".repeat(inner_width.saturating_sub(line.width())),
+                ));
+                line.style = line.style.bg(palette.cursor_line);
+            }
+            line
+        })
+        .collect();
+
+    let border = if focused {
+        Style::default()
+            .fg(palette.accent)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(palette.preview_border)
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border)
+        .title(" 📝 Markdown Source ")
+        .title_style(
+            Style::default()
+                .fg(palette.preview_border)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(Paragraph::new(visible).block(block), area);
+}
+
+/// One tab per open document, numbered for `3gt`. Tabs on the left give way
+/// when they don't all fit, so the current one stays visible.
+fn render_tab_bar(f: &mut Frame, app: &App, area: Rect) {
+    let palette = &app.config.colors;
+    let tabs: Vec<Span> = app
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(i, buffer)| {
+            let name = buffer.path.file_name().unwrap_or(buffer.path.as_os_str());
+            let label = format!(" {} {} ", i + 1, name.to_string_lossy());
+            if i == app.current_buffer {
+                let style = Style::default()
+                    .fg(palette.accent)
+                    .bg(palette.popup_selected)
+                    .add_modifier(Modifier::BOLD);
+                Span::styled(label, style)
+            } else {
+                Span::styled(label, Style::default().fg(palette.tree_guide))
+            }
+        })
+        .collect();
+
+    let current = app.current_buffer.min(tabs.len().saturating_sub(1));
+    let mut first = 0;
+    while first < current
+        && tabs[first..=current].iter().map(Span::width).sum::<usize>() > area.width as usize
+    {
+        first += 1;
+    }
+    f.render_widget(Paragraph::new(Line::from(tabs[first..].to_vec())), area);
+}
+
+/// Buffer picker popup listing the open documents.
+fn render_buffer_list(f: &mut Frame, app: &mut App) {
+    let palette = &app.config.colors;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Buffers ")
+        .border_style(Style::default().fg(palette.popup_border));
+
+    let area = centered_rect(60, 40, f.area());
+    f.render_widget(Clear, area); // Clear background
+
+    let items: Vec<ListItem> = app
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(i, buffer)| {
+            let path = buffer
+                .path
+                .strip_prefix(&app.tree.root)
+                .unwrap_or(&buffer.path);
+            let style = if i == app.current_buffer {
+                Style::default()
+                    .fg(palette.popup_current)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            ListItem::new(format!("{:>2}  {}", i + 1, path.display())).style(style)
+        })
+        .collect();
+
+    let list = List::new(items).block(block).highlight_style(
+        Style::default()
+            .bg(palette.popup_selected)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    f.render_stateful_widget(list, area, &mut app.buffer_list_state);
+}
+
+/// Outline popup listing the document's headings, indented by level.
+fn render_outline(f: &mut Frame, app: &mut App) {
+    let palette = &app.config.colors;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Outline ")
+        .border_style(Style::default().fg(palette.popup_border));
+
+    let area = centered_rect(60, 60, f.area());
+    f.render_widget(Clear, area); // Clear background
+
+    let current = app.current_heading();
+    let items: Vec<ListItem> = if app.headings.is_empty() {
+        vec![ListItem::new("No headings").style(Style::default().fg(palette.tree_guide))]
+    } else {
+        app.headings
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                let style = if Some(i) == current {
+                    Style::default()
+                        .fg(palette.popup_current)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let indent = "  ".repeat(h.level.saturating_sub(1));
+                ListItem::new(format!("{}{}", indent, h.text)).style(style)
+            })
+            .collect()
+    };
+
+    let list = List::new(items).block(block).highlight_style(
+        Style::default()
+            .bg(palette.popup_selected)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    f.render_stateful_widget(list, area, &mut app.outline_state);
+}
+
+/// Fuzzy finder popup: the query and ranked files on the left, the selected
+/// file rendered on the right.
+fn render_finder(f: &mut Frame, app: &mut App) {
+    let palette = app.config.colors.clone();
+    let area = centered_rect(80, 70, f.area());
+    f.render_widget(Clear, area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+        .split(area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Find File ")
+        .border_style(Style::default().fg(palette.popup_border));
+    let inner = block.inner(columns[0]);
+    f.render_widget(block, columns[0]);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let Some(finder) = app.finder.as_mut() else {
+        return;
+    };
+    let prompt = Line::from(vec![
+        Span::styled("> ", Style::default().fg(palette.accent)),
+        Span::raw(finder.query.clone()),
+        Span::styled("▏", Style::default().fg(palette.accent)),
+    ]);
+    f.render_widget(Paragraph::new(prompt), rows[0]);
+    f.render_widget(
+        Paragraph::new(format!("{}/{}", finder.matches.len(), finder.names.len()))
+            .style(Style::default().fg(palette.tree_guide))
+            .alignment(Alignment::Right),
+        rows[0],
+    );
+
+    let matched = Style::default()
+        .fg(palette.accent)
+        .add_modifier(Modifier::BOLD);
+    let items: Vec<ListItem> = finder
+        .matches
+        .iter()
+        .map(|m| {
+            let mut positions = m.positions.iter().peekable();
+            let spans: Vec<Span> = finder.names[m.file]
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    if positions.next_if_eq(&&i).is_some() {
+                        Span::styled(c.to_string(), matched)
+                    } else {
+                        Span::raw(c.to_string())
+                    }
+                })
+                .collect();
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .bg(palette.popup_selected)
+            .add_modifier(Modifier::BOLD),
+    );
+    f.render_stateful_widget(list, rows[1], &mut finder.state);
+
+    let title = finder
+        .selected_path()
+        .and_then(|p| p.file_name())
+        .map(|name| format!(" {} ", name.to_string_lossy()))
+        .unwrap_or_default();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(Style::default().fg(palette.popup_border));
+    let height = columns[1].height.saturating_sub(2) as usize;
+    let lines = finder
+        .preview(&app.current_theme, &app.config.markdown, columns[1])
+        .map(|r| {
+            r.text
+                .lines
+                .iter()
+                .take(height)
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    f.render_widget(Paragraph::new(lines).block(block), columns[1]);
+}
+
+/// Project search popup: the query, then one row per matching line with the
+/// file, line number and the line around the match.
+fn render_project_search(f: &mut Frame, app: &mut App) {
+    let palette = app.config.colors.clone();
+    let area = centered_rect(80, 70, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Search Project ")
+        .border_style(Style::default().fg(palette.popup_border));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let Some(search) = app.project_search.as_mut() else {
+        return;
+    };
+    let prompt = Line::from(vec![
+        Span::styled("> ", Style::default().fg(palette.accent)),
+        Span::raw(search.query.clone()),
+        Span::styled("▏", Style::default().fg(palette.accent)),
+    ]);
+    f.render_widget(Paragraph::new(prompt), rows[0]);
+    let (summary, color) = match &search.error {
+        Some(error) => (error.clone(), palette.error),
+        None if search.query.is_empty() => (String::new(), palette.tree_guide),
+        None => {
+            let plus = if search.truncated { "+" } else { "" };
+            (
+                format!("{}{} matches", search.results.len(), plus),
+                palette.tree_guide,
+            )
+        }
+    };
+    f.render_widget(
+        Paragraph::new(summary)
+            .style(Style::default().fg(color))
+            .alignment(Alignment::Right),
+        rows[0],
+    );
+
+    let items: Vec<ListItem> = search
+        .results
+        .iter()
+        .map(|result| {
+            let snippet = &result.snippet;
+            let range = result.highlight.clone();
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    search.names[result.file].clone(),
+                    Style::default().fg(palette.popup_current),
+                ),
+                Span::styled(
+                    format!(":{}  ", result.line),
+                    Style::default().fg(palette.tree_guide),
+                ),
+                Span::raw(snippet[..range.start].to_string()),
+                Span::styled(
+                    snippet[range.clone()].to_string(),
+                    Style::default().fg(Color::Black).bg(palette.search_match),
+                ),
+                Span::raw(snippet[range.end..].to_string()),
+            ]))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .bg(palette.popup_selected)
+            .add_modifier(Modifier::BOLD),
+    );
+    f.render_stateful_widget(list, rows[1], &mut search.state);
+}
+
+/// The bottom line: the `:` or search prompt while one is open, otherwise
+/// a message, the search or the open file on the left and the position in
+/// the document on the right.
+fn render_status_line(f: &mut Frame, app: &App, area: Rect) {
+    let palette = &app.config.colors;
+    let prompt = if let Some(prompt) = &app.command_prompt {
+        Some((":", prompt.input.clone()))
+    } else {
+        let prefix = |forward| if forward { "/" } else { "?" };
+        (app.search_prompt.as_ref()).map(|prompt| (prefix(prompt.forward), prompt.input.clone()))
+    };
+    if let Some((prefix, input)) = prompt {
+        let line = Line::from(vec![
+            Span::raw(prefix),
+            Span::raw(input),
+            Span::styled("▏", Style::default().fg(palette.accent)),
+        ]);
+        f.render_widget(Paragraph::new(line), area);
+        return;
+    }
+
+    let line = if let Some(message) = &app.status_message {
+        let color = if message.is_error {
+            palette.error
+        } else {
+            palette.status
+        };
+        Line::from(Span::styled(
+            message.text.clone(),
+            Style::default().fg(color),
+        ))
+    } else if let Some(search) = &app.search {
+        let prefix = if search.forward { "/" } else { "?" };
+        let position = match search.current {
+            Some(i) => format!("match {}/{}", i + 1, search.matches.len()),
+            None => format!("{} matches", search.matches.len()),
+        };
+        Line::from(vec![
+            Span::styled(
+                format!("{}{}", prefix, search.pattern),
+                Style::default().fg(palette.status),
+            ),
+            Span::raw("  "),
+            Span::styled(position, Style::default().add_modifier(Modifier::BOLD)),
+        ])
+    } else {
+        let name = match &app.current_file {
+            Some(path) => path
+                .strip_prefix(&app.tree.root)
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+            None => "[No file]".to_string(),
+        };
+        let mut spans = vec![Span::styled(
+            name,
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        if let Some(branch) = &app.git_branch {
+            spans.push(Span::styled(
+                format!("  ⎇ {}", branch),
+                Style::default().fg(palette.tree_guide),
+            ));
+        }
+        Line::from(spans)
+    };
+
+    let info = status_info(app);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(info.width() as u16)])
+        .split(area);
+    f.render_widget(Paragraph::new(line), chunks[0]);
+    f.render_widget(Paragraph::new(info), chunks[1]);
+}
+
+/// Right side of the status line: keys typed so far, theme, word count,
+/// cursor line and how far down the view is.
+fn status_info(app: &App) -> Line<'static> {
+    let palette = &app.config.colors;
+    let dim = Style::default().fg(palette.tree_guide);
+    let mut spans = Vec::new();
+    // Count and keys typed so far of a multi-key binding, like vim's showcmd.
+    if app.count.is_some() || !app.pending_keys.is_empty() {
+        let count = app.count.map(|n| n.to_string()).unwrap_or_default();
+        spans.push(Span::styled(
+            count + &format_keys(&app.pending_keys),
+            Style::default().fg(palette.accent),
+        ));
+        spans.push(Span::raw("  "));
+    }
+    spans.push(Span::styled(app.current_theme.clone(), dim));
+    if app.current_file.is_some()
+        && let Some(rendered) = app.rendered()
+    {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(format!("{} words", rendered.word_count), dim));
+        spans.push(Span::raw("  "));
+        spans.push(Span::raw(format!(
+            "Ln {}/{}",
+            app.cursor_line + 1,
+            rendered.text.lines.len()
+        )));
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(
+            scroll_position(app),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans.push(Span::raw(" "));
+    Line::from(spans)
+}
+
+/// How far down the view is, as in vim's ruler.
+fn scroll_position(app: &App) -> String {
+    match (app.scroll_offset, app.max_scroll) {
+        (_, 0) => "All".to_string(),
+        (0, _) => "Top".to_string(),
+        (top, max) if top >= max => "Bot".to_string(),
+        (top, max) => format!("{}%", top as usize * 100 / max as usize),
+    }
+}
+
+/// Completion candidates for the `:` prompt, on the row above it with the
+/// selected one highlighted.
+fn render_completions(f: &mut Frame, app: &App, status_area: Rect) {
+    let Some(completion) = app
+        .command_prompt
+        .as_ref()
+        .and_then(|p| p.completion.as_ref())
+    else {
+        return;
+    };
+    if status_area.y == 0 {
+        return;
+    }
+    let area = Rect {
+        y: status_area.y - 1,
+        ..status_area
+    };
+    let palette = &app.config.colors;
+
+    // Drop candidates from the front until the selected one fits.
+    let widths: Vec<usize> = completion
+        .candidates
+        .iter()
+        .map(|c| c.width() + 2)
+        .collect();
+    let mut first = 0;
+    while first < completion.selected
+        && widths[first..=completion.selected].iter().sum::<usize>() > area.width as usize
+    {
+        first += 1;
+    }
+
+    let spans: Vec<Span> = completion.candidates[first..]
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            let style = if first + i == completion.selected {
+                Style::default()
+                    .bg(palette.popup_selected)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Span::styled(format!(" {} ", candidate), style)
+        })
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(Line::from(spans)).style(Style::default().fg(palette.status)),
+        area,
+    );
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
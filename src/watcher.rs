@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// What changed on disk since the last poll.
+#[derive(Default)]
+pub struct Changes {
+    /// The open document was written, replaced or removed.
+    pub file_changed: bool,
+    /// Entries were added, removed or renamed in a watched directory.
+    pub tree_changed: bool,
+}
+
+/// Watches the open document and the loaded tree directories (inotify on Linux).
+///
+/// Directories are watched rather than the file itself, so editors that save
+/// by writing a new file and renaming it over the old one are still noticed.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<Event>>,
+    watched_dirs: HashSet<PathBuf>,
+    /// Canonical path of the open document.
+    file: Option<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Returns `None` if the platform watcher can't be created (e.g. the
+    /// inotify instance limit is reached); mdvim then simply doesn't reload.
+    pub fn new() -> Option<Self> {
+        let (tx, rx) = channel();
+        let watcher = notify::recommended_watcher(tx).ok()?;
+        Some(Self {
+            watcher,
+            rx,
+            watched_dirs: HashSet::new(),
+            file: None,
+        })
+    }
+
+    /// Watch exactly `dirs` plus the directory containing `file`.
+    pub fn sync(&mut self, dirs: Vec<PathBuf>, file: Option<&Path>) {
+        self.file = file.and_then(canonical_file_path);
+
+        let mut wanted: HashSet<PathBuf> = dirs
+            .iter()
+            .filter_map(|d| fs::canonicalize(d).ok())
+            .collect();
+        if let Some(parent) = self.file.as_ref().and_then(|f| f.parent()) {
+            wanted.insert(parent.to_path_buf());
+        }
+
+        for dir in self.watched_dirs.difference(&wanted) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        for dir in wanted.difference(&self.watched_dirs) {
+            let _ = self.watcher.watch(dir, RecursiveMode::NonRecursive);
+        }
+        self.watched_dirs = wanted;
+    }
+
+    /// Drain pending filesystem events without blocking.
+    pub fn poll(&mut self) -> Changes {
+        let mut changes = Changes::default();
+        while let Ok(result) = self.rx.try_recv() {
+            let Ok(event) = result else {
+                continue;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+
+            if self
+                .file
+                .as_ref()
+                .is_some_and(|file| event.paths.iter().any(|p| p == file))
+            {
+                changes.file_changed = true;
+            }
+
+            let structural = matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(notify::event::ModifyKind::Name(_))
+            );
+            if structural {
+                changes.tree_changed = true;
+            }
+        }
+        changes
+    }
+}
+
+/// Canonicalize a file path via its parent directory, so it still works for
+/// files that are momentarily missing during a save.
+fn canonical_file_path(path: &Path) -> Option<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(fs::canonicalize(parent).ok()?.join(path.file_name()?))
+}